@@ -75,6 +75,47 @@ fn distance_squared(x: vec3<f32>, y: vec3<f32>) -> f32 {
     return dot(v, v);
 }
 
+let SUBVOXEL_HALF_DIAGONAL: f32 = 0.8660254; // sqrt(3) / 2
+
+fn subvoxel_size() -> f32 {
+    return bind::cascade_info.tile_size / f32(consts::VOXELS_PER_TILE_DIM * consts::SUBVOXELS_PER_VOXEL_DIM);
+}
+
+// negate distances inside closed surfaces. the magnitude is clamped so we never overestimate the distance to the
+// surface from inside either
+fn apply_sign(lower: f32, interior: bool) -> f32 {
+    if interior {
+        return -max(lower, 0.0);
+    }
+    return lower;
+}
+
+// distance to the seed subvoxel centre a nearest_jfa value points at, or a large distance if there is no seed
+fn seed_distance(local_voxel: vec3<u32>, target_point: vec3<f32>, source_data: vec4<i32>) -> f32 {
+    var dist = bind::cascade_info.tile_size * f32(consts::TILE_DIM_COUNT - 1u);
+    if source_data.a != 0 {
+        let local_seed_voxel = vec3<i32>(local_voxel) + source_data.xyz;
+        let subvoxel = subvoxel_index_to_subvoxel(u32(source_data.a - 1));
+        let local_seed_position = subvoxel_local_position(local_seed_voxel, subvoxel);
+        let dist_sq = distance_squared(local_seed_position, target_point);
+        dist = sqrt(dist_sq);
+    }
+    return dist;
+}
+
+// subvoxel bits of a seed voxel, with any channels past consts::SEED_WORDS zeroed (rg32uint loads with a = 1)
+fn load_seed(coords: vec3<i32>) -> vec4<u32> {
+    let seed = textureLoad(bind::seed_jfa, coords);
+    if consts::SEED_WORDS == 2u {
+        return vec4<u32>(seed.rg, 0u, 0u);
+    }
+    return seed;
+}
+
+fn seed_has_subvoxel(seed: vec4<u32>, shift: u32) -> bool {
+    return ((seed[shift / 32u] >> (shift % 32u)) & 1u) == 1u;
+}
+
 struct CheckResult {
     best_dist_sq: f32,
     write_value: vec4<i32>,
@@ -99,7 +140,7 @@ fn check_source(target_point: vec3<f32>, local_voxel: vec3<i32>, local_jump_sour
                 let seed_coords = voxel_local_to_maybe_grid(local_seed_voxel);
 
                 if seed_coords.x > -1 { // seed is within our live grid
-                    let seed_value = textureLoad(bind::seed_jfa, seed_coords).rg;
+                    let seed_value = load_seed(seed_coords);
 
                     // check if it can be better (not sure this really helps)
                     let seed_position = voxel_local_to_local_position(local_seed_voxel);
@@ -108,15 +149,8 @@ fn check_source(target_point: vec3<f32>, local_voxel: vec3<i32>, local_jump_sour
                         for (var sx=0u; sx<consts::SUBVOXELS_PER_VOXEL_DIM; sx++) {
                             for (var sy=0u; sy<consts::SUBVOXELS_PER_VOXEL_DIM; sy++) {
                                 for (var sz=0u; sz<consts::SUBVOXELS_PER_VOXEL_DIM; sz++) {
-                                    var seed_subvoxel: bool;
                                     let shift = (((sz * consts::SUBVOXELS_PER_VOXEL_DIM) + sy) * consts::SUBVOXELS_PER_VOXEL_DIM) + sx;
-                                    if shift < 32u {
-                                        seed_subvoxel = ((seed_value.r >> shift) & 1u) == 1u;
-                                    } else {
-                                        seed_subvoxel = ((seed_value.g >> (shift - 32u)) & 1u) == 1u;
-                                    }
-
-                                    if seed_subvoxel {
+                                    if seed_has_subvoxel(seed_value, shift) {
                                         let local_seed_position = subvoxel_local_position(local_seed_voxel, vec3<u32>(sx,sy,sz));
                                         let dist_sq = distance_squared(local_seed_position, target_point);
 
@@ -137,7 +171,7 @@ fn check_source(target_point: vec3<f32>, local_voxel: vec3<i32>, local_jump_sour
                 let dist_sq = distance_squared(local_seed_position, target_point);
                 let offset = local_seed_voxel - local_voxel;
 
-                if dist_sq < res.best_dist_sq && all(abs(offset) < vec3(128)) {
+                if dist_sq < res.best_dist_sq && all(abs(offset) < vec3(consts::NEAREST_OFFSET_LIMIT)) {
                     res.best_dist_sq = dist_sq;
                     res.write_value = vec4<i32>(offset, source_data.a);
                 }
@@ -9,14 +9,16 @@ var<storage> mesh_headers: types::MeshHeader;
 @group(0) @binding(1)
 var<storage> transforms: types::Transforms;
 
+// packed vertex positions, see MeshInstance::vertex_format
 @group(0) @binding(2)
-var<storage> vertex_positions: array<vec4<f32>>;
+var<storage> vertices: array<u32>;
 
 @group(0) @binding(3)
 var<uniform> cascade_info: types::CascadeInfo;
 
+// cascade-relative triangles for the current update, written by transform.wgsl
 @group(0) @binding(4)
-var<storage, read_write> coarse_processed_tris: array<types::ProcessedTri>;
+var<storage, read_write> transformed_tris: array<types::TransformedTri>;
 
 // x = tile id, y = tri count top, per virtual tile
 @group(0) @binding(5)
@@ -25,15 +27,40 @@ var<storage, read_write> coarse_tile_counts: array<vec2<u32>, consts::MAX_TILES>
 @group(0) @binding(6)
 var<storage, read_write> coarse_ids: array<u32, consts::MAX_ID_COUNT>;
 
-// subvoxel bits
+// subvoxel bits, consts::SEED_WORDS channels are used. see addressing::load_seed
+#ifdef SDF_SEED_RGBA32
+@group(0) @binding(7)
+var seed_jfa: texture_storage_3d<rgba32uint, read_write>;
+#else
 @group(0) @binding(7)
 var seed_jfa: texture_storage_3d<rg32uint, read_write>;
+#endif
 
 // xyz = seed voxel offset
 // w = subvoxel index + 1 (or 0 for no seed found)
+// offsets are within +/- consts::NEAREST_OFFSET_LIMIT
+#ifdef SDF_NEAREST_RGBA16
+@group(0) @binding(8)
+var nearest_jfa: texture_storage_3d<rgba16sint, read_write>;
+#else
 @group(0) @binding(8)
 var nearest_jfa: texture_storage_3d<rgba8sint, read_write>;
+#endif
 
 @group(0) @binding(9)
 var<storage, read_write> fine_output: array<u32, consts::FINE_OUTPUT_SIZE>;
 
+// mesh-local vertex indices
+@group(0) @binding(10)
+var<storage> indices: array<u32>;
+
+// surface crossings, then interior bits, per voxel of each column, followed by a leaky bit per column.
+// see parity.wgsl for the layout
+@group(0) @binding(11)
+var<storage, read_write> sign_bits: array<atomic<u32>, consts::SIGN_WORD_COUNT>;
+
+// a bit per voxel, set inside closed surfaces. written by sign.wgsl when cascade_info.signed != 0,
+// see parity::interior_coords
+@group(0) @binding(12)
+var interior: texture_storage_3d<r32uint, read_write>;
+
@@ -1,12 +1,9 @@
 // scan triangles and allocate them to tiles for subsequent processing in fine.wgsl
-// also multiply meshes by transforms and write out the final vertex positions. this should be done up front instead.
+// triangles have already been moved into cascade space by transform.wgsl
+// runs as 4 passes: clear -> count (per triangle) -> scan (single workgroup) -> write (per triangle)
 
 // todo
-// - pretransform triangles
-//   - no need to write processed
 // split and duplicate with subsets of tiles regions?
-// parallel prefix sum
-// parallel init
 
 #import sdf::consts as consts
 #import sdf::bind as bind
@@ -15,8 +12,17 @@
 @group(1) @binding(0)
 var<storage, read_write> dispatch_indirect: vec3<u32>;
 
-var<workgroup> tile_counts: array<atomic<u32>, consts::TILE_COUNT>;
-var<workgroup> tile_write_index: array<atomic<u32>, consts::TILE_COUNT>;
+struct CoarseBins {
+    // ids per tile
+    counts: array<atomic<u32>, consts::TILE_COUNT>,
+    // ids written per tile so far
+    write_index: array<atomic<u32>, consts::TILE_COUNT>,
+    // start of each tile's ids in coarse_ids
+    offsets: array<u32, consts::TILE_COUNT>,
+}
+
+@group(1) @binding(1)
+var<storage, read_write> bins: CoarseBins;
 
 struct TileBbPair {
     min: vec3<u32>,
@@ -25,9 +31,10 @@ struct TileBbPair {
 
 fn tri_bounds(tri_id: u32) -> TileBbPair {
     let epsilon = 1e-5 * bind::cascade_info.tile_size;
+    let half_thickness = bind::transformed_tris[tri_id].half_thickness;
 
-    let min_pos_cascade = min(bind::coarse_processed_tris[tri_id].vertex_positions[0], min(bind::coarse_processed_tris[tri_id].vertex_positions[1], bind::coarse_processed_tris[tri_id].vertex_positions[2]));
-    let max_pos_cascade = max(bind::coarse_processed_tris[tri_id].vertex_positions[0], max(bind::coarse_processed_tris[tri_id].vertex_positions[1], bind::coarse_processed_tris[tri_id].vertex_positions[2])) + epsilon;
+    let min_pos_cascade = min(bind::transformed_tris[tri_id].vertex_positions[0], min(bind::transformed_tris[tri_id].vertex_positions[1], bind::transformed_tris[tri_id].vertex_positions[2])) - half_thickness;
+    let max_pos_cascade = max(bind::transformed_tris[tri_id].vertex_positions[0], max(bind::transformed_tris[tri_id].vertex_positions[1], bind::transformed_tris[tri_id].vertex_positions[2])) + epsilon + half_thickness;
 
     var res: TileBbPair;
     let min_tile = vec3<i32>(floor(min_pos_cascade.xyz / bind::cascade_info.tile_size));
@@ -38,122 +45,134 @@ fn tri_bounds(tri_id: u32) -> TileBbPair {
     return res;
 }
 
-fn process_tri(mesh_index: u32, local_vertex_id: u32, output_tri_id: u32) {
-    let origin = vec4<f32>(bind::cascade_info.origin) * bind::cascade_info.tile_size;
-    let transform = bind::transforms.t[bind::mesh_headers.index_offset_count[mesh_index].x];
-    var processed: types::ProcessedTri;
+fn tile_index(x: u32, y: u32, z: u32) -> u32 {
+    return (((z * consts::TILE_DIM_COUNT) + y) * consts::TILE_DIM_COUNT) + x;
+}
 
-    processed.vertex_positions[0] = transform * bind::vertex_positions[bind::mesh_headers.index_offset_count[mesh_index].y + local_vertex_id] - origin;
-    processed.vertex_positions[1] = transform * bind::vertex_positions[bind::mesh_headers.index_offset_count[mesh_index].y + local_vertex_id + 1u] - origin;
-    processed.vertex_positions[2] = transform * bind::vertex_positions[bind::mesh_headers.index_offset_count[mesh_index].y + local_vertex_id + 2u] - origin;
+let TRI_THREADS = 64u; // must match workgroup_size.x of the per-triangle passes
 
-    bind::coarse_processed_tris[output_tri_id] = processed;
+// the per-triangle dispatches are split over y for large triangle counts
+fn global_tri_id(g_id: vec3<u32>, num_workgroups: vec3<u32>) -> u32 {
+    return g_id.y * num_workgroups.x * TRI_THREADS + g_id.x;
 }
 
-fn tile_index(x: u32, y: u32, z: u32) -> u32 {
-    return (((z * consts::TILE_DIM_COUNT) + y) * consts::TILE_DIM_COUNT) + x;
+@compute @workgroup_size(256, 1, 1)
+fn coarse_clear(@builtin(global_invocation_id) g_id: vec3<u32>) {
+    if g_id.x < consts::TILE_COUNT {
+        atomicStore(&bins.counts[g_id.x], 0u);
+        atomicStore(&bins.write_index[g_id.x], 0u);
+    }
 }
 
-// modify the array to contain running sums
-// todo: make this parallel, we are on a gpu ffs
-fn prefix_sum(tid: u32) -> u32 {
-    var total_tile_writes = 0u;
+@compute @workgroup_size(64, 1, 1)
+fn coarse_count(@builtin(global_invocation_id) g_id: vec3<u32>, @builtin(num_workgroups) num_workgroups: vec3<u32>) {
+    let tri_id = global_tri_id(g_id, num_workgroups);
+    if tri_id >= bind::mesh_headers.tri_count {
+        return;
+    }
 
-    if tid == 0u {
-        var prev = 0u;
-        var next = 0u;
-        var index = 0u;
-        for (var tile=0u; tile<consts::TILE_COUNT; tile++) {
-            next = min(prev + atomicAdd(&tile_counts[tile], prev), consts::MAX_ID_COUNT);
-
-            while prev < next {
-                prev = min(next, prev + consts::MAX_IDS_PER_TILE);
-                bind::coarse_tile_counts[index].x = tile;
-                bind::coarse_tile_counts[index].y = prev;
-                index += 1u;
-                if index == consts::MAX_TILES {
-                    return index;
-                } 
+    let bbs = tri_bounds(tri_id);
+    for (var x=bbs.min.x; x<bbs.max.x; x += 1u) {
+        for (var y=bbs.min.y; y<bbs.max.y; y += 1u) {
+            for (var z=bbs.min.z; z<bbs.max.z; z += 1u) {
+                atomicAdd(&bins.counts[tile_index(x, y, z)], 1u);
             }
         }
-
-        bind::coarse_tile_counts[index].x = 0xFFFFFFFFu;
-        return index;
     }
-
-    return 0u;
 }
 
-let COARSE_THREADS = 1024u; // must match workgroup_size.x
-@compute @workgroup_size(1024, 1, 1)
-fn coarse_raster(@builtin(local_invocation_index) invocation_id: u32) {
-    // loop to count tris per tile
-    var tri_id = invocation_id;
-    var local_vertex_id = tri_id * 3u;
-    var mesh_index = 0u;
-
-    // init counts
-    if invocation_id == 0u {
-        for (var i=0u; i<consts::TILE_COUNT; i++) {
-            atomicStore(&tile_counts[i], 0u);
-            atomicStore(&tile_write_index[i], 0u);
-        }
-    }
+let SCAN_THREADS = 1024u; // must match workgroup_size.x of coarse_scan
+var<workgroup> scan_scratch: array<u32, 1024>;
 
+// returns the exclusive prefix sum over the workgroup, scan_scratch[SCAN_THREADS - 1u] holds the total afterwards
+fn exclusive_scan(tid: u32, value: u32) -> u32 {
+    // previous results may still be being read
+    workgroupBarrier();
+    scan_scratch[tid] = value;
     workgroupBarrier();
 
-    while tri_id < bind::mesh_headers.tri_count {
-        while local_vertex_id >= bind::mesh_headers.index_offset_count[mesh_index].z {
-            local_vertex_id -= bind::mesh_headers.index_offset_count[mesh_index].z;
-            mesh_index += 1u;
+    for (var offset = 1u; offset < SCAN_THREADS; offset *= 2u) {
+        var add = 0u;
+        if tid >= offset {
+            add = scan_scratch[tid - offset];
         }
+        workgroupBarrier();
+        scan_scratch[tid] += add;
+        workgroupBarrier();
+    }
 
-        process_tri(mesh_index, local_vertex_id, tri_id);
-        let bbs = tri_bounds(tri_id);
+    return scan_scratch[tid] - value;
+}
 
-        for (var x=bbs.min.x; x<bbs.max.x; x += 1u) {
-            for (var y=bbs.min.y; y<bbs.max.y; y += 1u) {
-                for (var z=bbs.min.z; z<bbs.max.z; z += 1u) {
-                    atomicAdd(&tile_counts[tile_index(x, y, z)], 1u);
-                }        
-            }
+// build the tile offsets and split tiles into virtual tiles of at most MAX_IDS_PER_TILE ids
+// each thread handles a contiguous run of tiles
+@compute @workgroup_size(1024, 1, 1)
+fn coarse_scan(@builtin(local_invocation_index) tid: u32) {
+    let tiles_per_thread = (consts::TILE_COUNT + SCAN_THREADS - 1u) / SCAN_THREADS;
+    let first_tile = min(tid * tiles_per_thread, consts::TILE_COUNT);
+    let end_tile = min(first_tile + tiles_per_thread, consts::TILE_COUNT);
+
+    var id_count = 0u;
+    for (var tile = first_tile; tile < end_tile; tile++) {
+        id_count += atomicLoad(&bins.counts[tile]);
+    }
+    let id_start = exclusive_scan(tid, id_count);
+
+    // id ranges are clamped to the coarse_ids buffer, so virtual tile counts depend on the id offsets
+    var virtual_count = 0u;
+    var start = id_start;
+    for (var tile = first_tile; tile < end_tile; tile++) {
+        let end = start + atomicLoad(&bins.counts[tile]);
+        let clamped_start = min(start, consts::MAX_ID_COUNT);
+        let clamped_end = min(end, consts::MAX_ID_COUNT);
+        bins.offsets[tile] = clamped_start;
+        virtual_count += (clamped_end - clamped_start + consts::MAX_IDS_PER_TILE - 1u) / consts::MAX_IDS_PER_TILE;
+        start = end;
+    }
+    let virtual_start = exclusive_scan(tid, virtual_count);
+    let virtual_total = scan_scratch[SCAN_THREADS - 1u];
+
+    // x = tile id, y = end of the virtual tile's ids (the start is the previous virtual tile's end)
+    var index = virtual_start;
+    start = id_start;
+    for (var tile = first_tile; tile < end_tile; tile++) {
+        let end = start + atomicLoad(&bins.counts[tile]);
+        var prev = min(start, consts::MAX_ID_COUNT);
+        let next = min(end, consts::MAX_ID_COUNT);
+        while prev < next && index < consts::MAX_TILES {
+            prev = min(next, prev + consts::MAX_IDS_PER_TILE);
+            bind::coarse_tile_counts[index] = vec2<u32>(tile, prev);
+            index += 1u;
         }
-
-        tri_id += COARSE_THREADS;
-        local_vertex_id += COARSE_THREADS * 3u;
+        start = end;
     }
 
-    workgroupBarrier();
-
-    if invocation_id == 0u {
-        let invocations = prefix_sum(invocation_id);
-        dispatch_indirect = vec3<u32>(invocations, 1u, 1u);
+    if tid == 0u {
+        let total = min(virtual_total, consts::MAX_TILES);
+        if total < consts::MAX_TILES {
+            bind::coarse_tile_counts[total].x = 0xFFFFFFFFu;
+        }
+        dispatch_indirect = vec3<u32>(total, 1u, 1u);
     }
+}
 
-    workgroupBarrier();
+@compute @workgroup_size(64, 1, 1)
+fn coarse_write(@builtin(global_invocation_id) g_id: vec3<u32>, @builtin(num_workgroups) num_workgroups: vec3<u32>) {
+    let tri_id = global_tri_id(g_id, num_workgroups);
+    if tri_id >= bind::mesh_headers.tri_count {
+        return;
+    }
 
-    // write tri indexes out
-    tri_id = invocation_id;
-    
-    while tri_id < bind::mesh_headers.tri_count {
-        let bbs = tri_bounds(tri_id);
-
-        for (var x=bbs.min.x; x<bbs.max.x; x += 1u) {
-            for (var y=bbs.min.y; y<bbs.max.y; y += 1u) {
-                for (var z=bbs.min.z; z<bbs.max.z; z += 1u) {
-                    let tile = tile_index(x,y,z);
-                    var start_offset = 0u;
-                    if tile > 0u {
-                        start_offset = tile_counts[tile - 1u];
-                    }
-                    let write_index = atomicAdd(&tile_write_index[tile], 1u);
-                    if start_offset + write_index < consts::MAX_ID_COUNT {
-                        bind::coarse_ids[start_offset + write_index] = tri_id;
-                    }
-                }        
+    let bbs = tri_bounds(tri_id);
+    for (var x=bbs.min.x; x<bbs.max.x; x += 1u) {
+        for (var y=bbs.min.y; y<bbs.max.y; y += 1u) {
+            for (var z=bbs.min.z; z<bbs.max.z; z += 1u) {
+                let tile = tile_index(x, y, z);
+                let write_index = bins.offsets[tile] + atomicAdd(&bins.write_index[tile], 1u);
+                if write_index < consts::MAX_ID_COUNT {
+                    bind::coarse_ids[write_index] = tri_id;
+                }
             }
         }
-
-        tri_id += COARSE_THREADS;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,56 @@
+#define_import_path sdf::encoding
+
+// convert distances to and from the values stored in the output texture, for consts::OUTPUT_FORMAT
+// (see SdfOutputFormat). no bindings, so sampling shaders with their own layouts can import it
+
+#import sdf::consts as consts
+#import sdf::types as types
+
+// truncation band in world units, 0 for unclamped
+fn truncation_band(info: types::CascadeInfo) -> f32 {
+    return f32(info.truncation) * info.tile_size / f32(consts::VOXELS_PER_TILE_DIM);
+}
+
+fn encode_distance(dist: f32, info: types::CascadeInfo) -> f32 {
+    let band = truncation_band(info);
+    if band == 0.0 {
+        // only the float formats are unclamped
+        return dist;
+    }
+
+    let clamped = clamp(dist, -band, band);
+    if consts::OUTPUT_FORMAT == consts::OUTPUT_FORMAT_R8SNORM {
+        return clamped / band;
+    }
+    if consts::OUTPUT_FORMAT == consts::OUTPUT_FORMAT_R8UNORM {
+        return clamped / band * 0.5 + 0.5;
+    }
+    return clamped;
+}
+
+fn decode_distance(value: f32, info: types::CascadeInfo) -> f32 {
+    let band = truncation_band(info);
+    if consts::OUTPUT_FORMAT == consts::OUTPUT_FORMAT_R8SNORM {
+        return value * band;
+    }
+    if consts::OUTPUT_FORMAT == consts::OUTPUT_FORMAT_R8UNORM {
+        return (value * 2.0 - 1.0) * band;
+    }
+    return value;
+}
+
+// largest difference between a distance and its decoded value, within the band
+fn encoding_error(dist: f32, info: types::CascadeInfo) -> f32 {
+    let band = truncation_band(info);
+    if consts::OUTPUT_FORMAT == consts::OUTPUT_FORMAT_R8SNORM {
+        return band / 254.0;
+    }
+    if consts::OUTPUT_FORMAT == consts::OUTPUT_FORMAT_R8UNORM {
+        return band / 255.0;
+    }
+    if consts::OUTPUT_FORMAT == consts::OUTPUT_FORMAT_R16FLOAT {
+        // 10 bit mantissa
+        return abs(dist) / 2048.0;
+    }
+    return 0.0;
+}
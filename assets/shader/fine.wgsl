@@ -1,18 +1,19 @@
 // fill subvoxels within the tile
 
+// each triangle is swept along its dominant axis, with the overlapping span of each row found directly from
+// the separating axis tests (see intersect::subvoxel_span), so no per-subvoxel tests are needed
+
 // todo 
-// * run bounds by bb, 
-// - remove bb test in intersect (currently bounds are per voxel, need to move to subvoxel)
-// - precompute axis test per axis pair
 // - pick output format for subvoxel count
 
 #import sdf::consts as consts
 #import sdf::bind as bind
 #import sdf::intersect as intersect
 #import sdf::addressing as addr
+#import sdf::parity as parity
 
-var<workgroup> voxels_r: array<atomic<u32>, consts::VOXELS_PER_TILE>;
-var<workgroup> voxels_g: array<atomic<u32>, consts::VOXELS_PER_TILE>;
+// consts::SEED_WORDS words of subvoxel bits per voxel
+var<workgroup> voxel_bits: array<atomic<u32>, consts::SEED_WORDS_PER_TILE>;
 
 @compute @workgroup_size(128,1,1)
 fn fine_raster(@builtin(workgroup_id) workgroup_id: vec3<u32>, @builtin(local_invocation_index) thread_id: u32) {
@@ -25,9 +26,8 @@ fn fine_raster(@builtin(workgroup_id) workgroup_id: vec3<u32>, @builtin(local_in
 
     if thread_id == 0u {
         // init our local texture
-        for (var i = 0u; i < consts::VOXELS_PER_TILE; i++) {
-            atomicStore(&voxels_r[i], 0u);
-            atomicStore(&voxels_g[i], 0u);
+        for (var i = 0u; i < consts::SEED_WORDS_PER_TILE; i++) {
+            atomicStore(&voxel_bits[i], 0u);
         }
     }
 
@@ -51,47 +51,42 @@ fn fine_raster(@builtin(workgroup_id) workgroup_id: vec3<u32>, @builtin(local_in
         let bb_min = vec3<u32>(max(vec3<i32>(0), vec3<i32>(floor((tri.min - tile_min) / subv))));
         let bb_max = vec3<u32>(min(vec3<i32>(i32(consts::VOXELS_PER_TILE_DIM * consts::SUBVOXELS_PER_VOXEL_DIM)), vec3<i32>(ceil((tri.max - tile_min + epsilon) / subv))));
 
-        for (var x=bb_min.x; x < bb_max.x; x++) {
-            for (var y=bb_min.y; y < bb_max.y; y++) {
-                let z_range = intersect::subvoxel_z_range(tile_min, x, y, subv, &tri);
-                for (var z=max(bb_min.z, z_range.x); z < min(bb_max.z, z_range.y); z++) {
-                // for (var z=bb_min.z; z < bb_max.z; z++) {
-                    let voxel = vec3<u32>(x, y, z) / consts::SUBVOXELS_PER_VOXEL_DIM;
-                    let subvoxel = vec3<u32>(x, y, z) % consts::SUBVOXELS_PER_VOXEL_DIM;
+        // iterate rows over the 2 minor axes, with the dominant axis innermost
+        let d = tri.dominant;
+        let a = (d + 1u) % 3u;
+        let b = (d + 2u) % 3u;
+
+        for (var i=bb_min[a]; i < bb_max[a]; i++) {
+            for (var j=bb_min[b]; j < bb_max[b]; j++) {
+                var pos = vec3<u32>(0u);
+                pos[a] = i;
+                pos[b] = j;
+
+                let span = intersect::subvoxel_span(tile_min, pos, subv, &tri);
+                for (var k=max(bb_min[d], span.x); k < min(bb_max[d], span.y); k++) {
+                    pos[d] = k;
+                    let voxel = pos / consts::SUBVOXELS_PER_VOXEL_DIM;
+                    let subvoxel = pos % consts::SUBVOXELS_PER_VOXEL_DIM;
 
                     let index = (((voxel.z * consts::VOXELS_PER_TILE_DIM) + voxel.y) * consts::VOXELS_PER_TILE_DIM) + voxel.x;
-                    let target_point = tile_min + (vec3<f32>(voxel * consts::SUBVOXELS_PER_VOXEL_DIM + subvoxel) + vec3<f32>(0.5)) * subv;
-
-                    if intersect::voxel_test(target_point, &tri) {
-                        let shift = (((subvoxel.z * consts::SUBVOXELS_PER_VOXEL_DIM) + subvoxel.y) * consts::SUBVOXELS_PER_VOXEL_DIM) + subvoxel.x;
-                        if shift < 32u {
-                            atomicOr(&voxels_r[index], 1u << shift);
-                        } else {
-                            atomicOr(&voxels_g[index], 1u << (shift - 32u));
-                        }
-                    }
+                    let shift = (((subvoxel.z * consts::SUBVOXELS_PER_VOXEL_DIM) + subvoxel.y) * consts::SUBVOXELS_PER_VOXEL_DIM) + subvoxel.x;
+                    atomicOr(&voxel_bits[index * consts::SEED_WORDS + shift / 32u], 1u << (shift % 32u));
                 }
             }                    
         }
+
+        if bind::cascade_info.signed != 0u {
+            parity::record_crossings(&tri, tile_xyz);
+        }
     }
 
     workgroupBarrier();
 
     // write to seed output
     if thread_id == 0u {
-        let sdf_tile_offset = workgroup_id.x * consts::VOXELS_PER_TILE;
-        for (var x=0u; x < consts::VOXELS_PER_TILE_DIM; x++) {
-            for (var y=0u; y < consts::VOXELS_PER_TILE_DIM; y++) {
-                for (var z=0u; z < consts::VOXELS_PER_TILE_DIM; z++) {
-                    let index = (((z * consts::VOXELS_PER_TILE_DIM) + y) * consts::VOXELS_PER_TILE_DIM) + x;
-
-                    let r = atomicLoad(&voxels_r[index]);
-                    let g = atomicLoad(&voxels_g[index]);
-
-                    bind::fine_output[(index + sdf_tile_offset) * 2u] = r;
-                    bind::fine_output[(index + sdf_tile_offset) * 2u + 1u] = g;
-                }
-            }
+        let sdf_tile_offset = workgroup_id.x * consts::SEED_WORDS_PER_TILE;
+        for (var i = 0u; i < consts::SEED_WORDS_PER_TILE; i++) {
+            bind::fine_output[sdf_tile_offset + i] = atomicLoad(&voxel_bits[i]);
         }
     }
 }
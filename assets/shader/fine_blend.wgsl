@@ -1,70 +1,91 @@
 // merge fine output into a single final seed texture
-
-// todo
-// - parallel over workgroups with i division, take up to end of your working tile when you reach upper limit
+// runs a workgroup per real tile, each merging the run of virtual tiles coarse.wgsl created for that tile
 
 #import sdf::consts as consts
 #import sdf::bind as bind
 #import sdf::addressing as addr
 
+// written by coarse_scan, x = virtual tile count
+@group(1) @binding(0)
+var<storage, read_write> dispatch_indirect: vec3<u32>;
 
-@compute @workgroup_size(8,8,8)
-//@compute @workgroup_size(4,4,4)
-fn fine_blend(@builtin(local_invocation_id) invocation_id: vec3<u32>, @builtin(local_invocation_index) thread_id: u32) {
-    var current_tile = 0u;
-    var data = vec2<u32>(0u);
-    let redraw = bind::cascade_info.redraw.xyz;
-
-    for (var i=0u; i<consts::MAX_TILES; i++) {
-        let next_tile = bind::coarse_tile_counts[i].x;
-
-        for (; current_tile < min(next_tile, consts::TILE_COUNT); current_tile++) {
-            let local_tile = addr::tile_index_to_local(current_tile);
+// virtual tiles are written in tile order, so find the first one for our tile with a binary search
+fn first_virtual_tile(tile: u32, virtual_count: u32) -> u32 {
+    var low = 0u;
+    var high = virtual_count;
+    while low < high {
+        let mid = (low + high) / 2u;
+        if bind::coarse_tile_counts[mid].x < tile {
+            low = mid + 1u;
+        } else {
+            high = mid;
+        }
+    }
+    return low;
+}
 
-            let valid = 
-                redraw == vec3<i32>(0) 
-                || 
-                (redraw < vec3<i32>(0) && vec3<i32>(local_tile) < -redraw)
-                ||
-                (redraw > vec3<i32>(0) && local_tile >= consts::TILE_DIM_COUNT - vec3<u32>(redraw))
-            ;
+// a thread per voxel, voxels_per_tile_dim is at most 8
+@compute @workgroup_size(8,8,8)
+fn fine_blend(@builtin(workgroup_id) workgroup_id: vec3<u32>, @builtin(local_invocation_index) thread_id: u32) {
+    if thread_id >= consts::VOXELS_PER_TILE {
+        return;
+    }
+    let tile_voxel = vec3<u32>(
+        thread_id % consts::VOXELS_PER_TILE_DIM,
+        (thread_id / consts::VOXELS_PER_TILE_DIM) % consts::VOXELS_PER_TILE_DIM,
+        thread_id / (consts::VOXELS_PER_TILE_DIM * consts::VOXELS_PER_TILE_DIM),
+    );
 
-            if !all(valid) {
-                data = vec2<u32>(0u, 0u);
-                continue;
-            }
+    let current_tile = workgroup_id.x;
+    let update_min = bind::cascade_info.update_min.xyz;
+    let update_max = bind::cascade_info.update_max.xyz;
+    // no scroll -> rebuilding a dirty region in place
+    let in_place = all(bind::cascade_info.redraw.xyz == vec3<i32>(0));
 
-            let write_index = addr::voxel_local_to_grid(local_tile * consts::VOXELS_PER_TILE_DIM + invocation_id);
+    let local_tile = addr::tile_index_to_local(current_tile);
+    let valid = vec3<i32>(local_tile) >= update_min && vec3<i32>(local_tile) < update_max;
+    let write_index = addr::voxel_local_to_grid(local_tile * consts::VOXELS_PER_TILE_DIM + tile_voxel);
 
-            textureStore(
-                bind::seed_jfa,
-                write_index, 
-                vec4<u32>(data, 0u, 1u)
-            );
-            // subvoxel index 0 -> no seed, [1..SUBVOXELS^3] -> seed subvoxel index, SUBVOXELS^3+1 -> seed here but uninit
-            var write_seed = i32(consts::SUBVOXELS_PER_VOXEL_DIM * consts::SUBVOXELS_PER_VOXEL_DIM * consts::SUBVOXELS_PER_VOXEL_DIM + 1u);
-            if all(data == vec2<u32>(0u)) {
-                write_seed = 0;
+    if !all(valid) {
+        if in_place {
+            // seeds outside the region are kept, but any flooded values may point to seeds we are
+            // about to remove so they are reset for the jfa to recalculate
+            let nearest = textureLoad(bind::nearest_jfa, write_index);
+            if any(nearest.xyz != vec3<i32>(0)) {
+                textureStore(bind::nearest_jfa, write_index, vec4<i32>(0));
             }
-            // xyz = 0, a != 0 -> seed is self
-            textureStore(
-                bind::nearest_jfa,
-                write_index, 
-                vec4<i32>(vec3<i32>(0), write_seed)
-            );
-
-            data = vec2<u32>(0u, 0u);
         }
+        return;
+    }
 
-        if next_tile == 0xFFFFFFFFu {
-            i = consts::MAX_TILES;
-            continue;
+    let virtual_count = min(dispatch_indirect.x, consts::MAX_TILES);
+    var data = vec4<u32>(0u);
+    for (var i = first_virtual_tile(current_tile, virtual_count); i < virtual_count; i++) {
+        if bind::coarse_tile_counts[i].x != current_tile {
+            break;
         }
 
-        let sdf_tile_offset = i * consts::VOXELS_PER_TILE;
-        let index = sdf_tile_offset + thread_id;
+        let index = i * consts::SEED_WORDS_PER_TILE + thread_id * consts::SEED_WORDS;
+        for (var word = 0u; word < consts::SEED_WORDS; word++) {
+            data[word] |= bind::fine_output[index + word];
+        }
+    }
 
-        data.x |= bind::fine_output[index*2u];
-        data.y |= bind::fine_output[index*2u+1u];
+    // channels past SEED_WORDS are dropped by rg32uint
+    textureStore(
+        bind::seed_jfa,
+        write_index, 
+        data
+    );
+    // subvoxel index 0 -> no seed, [1..SUBVOXELS^3] -> seed subvoxel index, SUBVOXELS^3+1 -> seed here but uninit
+    var write_seed = i32(consts::SUBVOXELS_PER_VOXEL_DIM * consts::SUBVOXELS_PER_VOXEL_DIM * consts::SUBVOXELS_PER_VOXEL_DIM + 1u);
+    if all(data == vec4<u32>(0u)) {
+        write_seed = 0;
     }
-}
\ No newline at end of file
+    // xyz = 0, a != 0 -> seed is self
+    textureStore(
+        bind::nearest_jfa,
+        write_index, 
+        vec4<i32>(vec3<i32>(0), write_seed)
+    );
+}
@@ -3,23 +3,50 @@
 #import sdf::bind as bind
 #import sdf::consts as consts
 
+// separating axes for subvoxel / triangle overlap, besides the 3 box axes (handled by the bounding box):
+// 0 = triangle normal, 1-9 = each edge crossed with each box axis
+// the edge axes lie in the axis-aligned planes, so they are the triangle's edge normals in each 2d projection
+let SAT_AXIS_COUNT = 10u;
+
 struct TriAccel {
     v: array<vec3<f32>, 3>,
     e: array<vec3<f32>, 3>,
     min: vec3<f32>,
     max: vec3<f32>,
     n: vec3<f32>,
-    plane_corners: array<vec3<f32>, 2>,
     h: f32,
 
-    edge_axis: array<vec3<f32>, 3>,
-    edge_base: array<vec3<f32>, 3>,
-    edge_corners: array<vec3<f32>, 3>,
-    edge_dist_sq: array<f32, 3>,
+    // axis we sweep spans along, the largest component of the normal
+    dominant: u32,
+
+    // subvoxels centred at p overlap the triangle when dot(sat_axes[i], p) is within sat_ranges[i] for every axis
+    // (the ranges include the subvoxel's projected radius and the triangle's thickness)
+    sat_axes: array<vec3<f32>, SAT_AXIS_COUNT>,
+    sat_ranges: array<vec2<f32>, SAT_AXIS_COUNT>,
+}
+
+// projected radius along `axis` of the shape we test against the triangle (see SdfVoxelization): the subvoxel box,
+// or the diamond inscribed in it. only the triangle's axes are tested, so the diamond tests are slightly
+// conservative near the triangle's corners
+fn support_radius(axis: vec3<f32>, radius: f32, diamond: bool) -> f32 {
+    if diamond {
+        return radius * max(abs(axis.x), max(abs(axis.y), abs(axis.z)));
+    }
+    return radius * dot(abs(axis), vec3<f32>(1.0));
+}
+
+fn set_sat_axis(accel: ptr<function, TriAccel>, i: u32, axis: vec3<f32>, radius: f32, diamond: bool, thickness: vec3<f32>) {
+    let p0 = dot(axis, (*accel).v[0]);
+    let p1 = dot(axis, (*accel).v[1]);
+    let p2 = dot(axis, (*accel).v[2]);
+    let r = support_radius(axis, radius, diamond) + abs(dot(axis, thickness));
+
+    (*accel).sat_axes[i] = axis;
+    (*accel).sat_ranges[i] = vec2<f32>(min(p0, min(p1, p2)) - r, max(p0, max(p1, p2)) + r);
 }
 
 fn build_tri(id: u32) -> TriAccel {
-    let tri = bind::coarse_processed_tris[id];
+    let tri = bind::transformed_tris[id];
 
     var accel: TriAccel;
 
@@ -34,33 +61,37 @@ fn build_tri(id: u32) -> TriAccel {
     accel.n = normalize(cross_e0_e1);
     accel.h = 0.5 * bind::cascade_info.tile_size / f32(consts::VOXELS_PER_TILE_DIM * consts::SUBVOXELS_PER_VOXEL_DIM);
 
-    accel.min = min(min(accel.v[0], accel.v[1]), accel.v[2]);
-    accel.max = max(max(accel.v[0], accel.v[1]), accel.v[2]);
+    accel.min = min(min(accel.v[0], accel.v[1]), accel.v[2]) - tri.half_thickness;
+    accel.max = max(max(accel.v[0], accel.v[1]), accel.v[2]) + tri.half_thickness;
+
+    let abs_n = abs(cross_e0_e1);
+    if abs_n.x >= abs_n.y && abs_n.x >= abs_n.z {
+        accel.dominant = 0u;
+    } else if abs_n.y >= abs_n.z {
+        accel.dominant = 1u;
+    } else {
+        accel.dominant = 2u;
+    }
 
-    accel.plane_corners[0] = accel.v[0] + sign(accel.n) * accel.h;
-    accel.plane_corners[1] = accel.v[0] - sign(accel.n) * accel.h;
+    // pad the subvoxel a little so we stay conservative under rounding
+    let radius = accel.h + 1e-5 * bind::cascade_info.tile_size;
+
+    // the slab's half extent along the normal. degenerate triangles have no normal, and aren't thickened
+    let thickness = cross_e0_e1 * (tri.half_thickness / max(length(cross_e0_e1), 1e-20));
+
+    // the separating modes test the plane and / or the 2d projections against the subvoxel's inscribed diamond
+    let mode = bind::cascade_info.voxelization;
+    let plane_diamond = mode == consts::VOXELIZATION_SIX_SEPARATING;
+    let edge_diamond = mode != consts::VOXELIZATION_CONSERVATIVE;
+
+    // unnormalized so degenerate triangles don't produce nans
+    set_sat_axis(&accel, 0u, cross_e0_e1, radius, plane_diamond, thickness);
 
     for (var i=0u; i<3u; i++) {
-        let edge = i;
-        let vert = (i + 2u) % 3u;
-
-        // project v0->v2 (== -e[2]) onto v0->v1 (== e[0])
-        let intercept = -dot(accel.e[vert], accel.e[edge]);
-        // find base of axis on the v0->v1 edge
-        let base = accel.v[edge] + accel.e[edge] * intercept / dot(accel.e[edge], accel.e[edge]);
-        // top of axis is just the vertex
-        let tip = accel.v[vert];
-        // get the axis (don't normalize)
-        accel.edge_axis[i] = tip - base;
-
-        // signs to work out near and far corners
-        let axis_dir = sign(accel.edge_axis[i]);
-        // box corners
-        accel.edge_corners[i] = accel.h * axis_dir;
-        accel.edge_base[i] = base;
-
-        // far dist for test
-        accel.edge_dist_sq[i] = dot(accel.edge_axis[i], accel.edge_axis[i]);
+        let e = accel.e[i];
+        set_sat_axis(&accel, 1u + i * 3u, vec3<f32>(0.0, e.z, -e.y), radius, edge_diamond, thickness);
+        set_sat_axis(&accel, 2u + i * 3u, vec3<f32>(-e.z, 0.0, e.x), radius, edge_diamond, thickness);
+        set_sat_axis(&accel, 3u + i * 3u, vec3<f32>(e.y, -e.x, 0.0), radius, edge_diamond, thickness);
     }
 
     return accel;
@@ -70,27 +101,15 @@ fn voxel_bb_test(target_point: vec3<f32>, tri: ptr<function, TriAccel>) -> bool
     return all((*tri).min < target_point + (*tri).h && (*tri).max >= target_point - (*tri).h);
 }
 
-fn voxel_plane_test(target_point: vec3<f32>, tri: ptr<function, TriAccel>) -> bool {
-    let r1 = dot((*tri).n, target_point - (*tri).plane_corners[0]);
-    let r2 = dot((*tri).n, target_point - (*tri).plane_corners[1]);
-
-    return sign(r1) != sign(r2);
-}
-
-fn voxel_edge_test(target_point: vec3<f32>, tri: ptr<function, TriAccel>) -> bool {
-    for (var i=0u; i<3u; i++) {
-        let axis = (*tri).edge_axis[i];
-
-        // box corners
-        let far_corner = target_point + (*tri).edge_corners[i] - (*tri).edge_base[i];
-        let near_corner = target_point - (*tri).edge_corners[i] - (*tri).edge_base[i];
-
-        // project corners onto axis
-        let far = dot(far_corner, (*tri).edge_axis[i]);
-        let near = dot(near_corner, (*tri).edge_axis[i]);
+// single subvoxel test, the fine raster uses subvoxel_span instead
+fn voxel_test(target_point: vec3<f32>, tri: ptr<function, TriAccel>) -> bool {
+    if !voxel_bb_test(target_point, tri) {
+        return false;
+    }
 
-        // test
-        if (max(near, far) < 0.0) || (min(near, far) > (*tri).edge_dist_sq[i]) {
+    for (var i=0u; i<SAT_AXIS_COUNT; i++) {
+        let p = dot((*tri).sat_axes[i], target_point);
+        if p < (*tri).sat_ranges[i].x || p > (*tri).sat_ranges[i].y {
             return false;
         }
     }
@@ -98,40 +117,87 @@ fn voxel_edge_test(target_point: vec3<f32>, tri: ptr<function, TriAccel>) -> boo
     return true;
 }
 
-fn voxel_test(target_point: vec3<f32>, tri: ptr<function, TriAccel>) -> bool {
-    return
-        true
-        // && voxel_bb_test(target_point, tri) 
-        // && voxel_plane_test(target_point, tri) 
-        && voxel_edge_test(target_point, tri)
-        ;
+// range of subvoxel indices [x, y) along the triangle's dominant axis that overlap the triangle,
+// for the row of subvoxels through `subvoxel` (the dominant axis component is ignored)
+fn subvoxel_span(tile_min: vec3<f32>, subvoxel: vec3<u32>, subv: f32, tri: ptr<function, TriAccel>) -> vec2<u32> {
+    let d = (*tri).dominant;
+    var row_start = subvoxel;
+    row_start[d] = 0u;
+    let base_point = tile_min + (vec3<f32>(row_start) + vec3<f32>(0.5)) * subv;
+
+    let count = f32(consts::VOXELS_PER_TILE_DIM * consts::SUBVOXELS_PER_VOXEL_DIM);
+    var low = 0.0;
+    var high = count - 1.0;
+
+    for (var i=0u; i<SAT_AXIS_COUNT; i++) {
+        let axis = (*tri).sat_axes[i];
+        let range = (*tri).sat_ranges[i];
+        let base = dot(axis, base_point);
+        let slope = axis[d] * subv;
+
+        if abs(slope) < 1e-20 {
+            // constant along the row, check one point
+            if base < range.x || base > range.y {
+                return vec2<u32>(0u, 0u);
+            }
+        } else {
+            // solve for the subvoxel index (as f32) at each end of the range
+            let t0 = (range.x - base) / slope;
+            let t1 = (range.y - base) / slope;
+            low = max(low, min(t0, t1));
+            high = min(high, max(t0, t1));
+        }
+    }
+
+    if low > high {
+        return vec2<u32>(0u, 0u);
+    }
+
+    // low / high are clamped to the tile so the conversions can't overflow
+    return vec2<u32>(u32(ceil(low)), u32(floor(high)) + 1u);
 }
 
-// todo - base this on primary axis of the triangle for better efficiency
-// todo - filter x/y as well
-fn subvoxel_z_range(tile_min: vec3<f32>, subvoxel_x: u32, subvoxel_y: u32, subv: f32, tri: ptr<function, TriAccel>) -> vec2<u32> {
-    let target_point = tile_min + (vec3<f32>(vec3<u32>(subvoxel_x, subvoxel_y, 0u)) + vec3<f32>(0.5)) * subv;
+// closest point to p on the triangle abc (from real-time collision detection, ericson)
+fn closest_point_on_tri(p: vec3<f32>, a: vec3<f32>, b: vec3<f32>, c: vec3<f32>) -> vec3<f32> {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = dot(ab, ap);
+    let d2 = dot(ac, ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
 
-    if abs((*tri).n.z) < 1e-20 {
-        // runs parallel, check one point
-        if voxel_plane_test(target_point, tri) {
-            return vec2<u32>(0u, consts::SUBVOXELS_PER_VOXEL_DIM * consts::VOXELS_PER_DIM);
-        } else {
-            return vec2<u32>(1u, 0u);
-        }
+    let bp = p - b;
+    let d3 = dot(ab, bp);
+    let d4 = dot(ac, bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
     }
 
-    // get plane test results for first subvoxel in the z-row
-    let r1 = dot((*tri).n, target_point - (*tri).plane_corners[0]);
-    let r2 = dot((*tri).n, target_point - (*tri).plane_corners[1]);
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
 
-    // use the delta per subvoxel to find the subvoxel index (as f32) where they touch the tri plane
-    let near_zero = -r2 / ((*tri).n.z * subv);
-    let far_zero = -r1 / ((*tri).n.z * subv);
+    let cp = p - c;
+    let d5 = dot(ab, cp);
+    let d6 = dot(ac, cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
 
-    // clamp our test range to those where the signs will differ for the 2 corners
-    let low_point = u32(clamp(i32(ceil(min(far_zero, near_zero))), 0, i32(consts::SUBVOXELS_PER_VOXEL_DIM * consts::VOXELS_PER_DIM)));
-    let hi_point = u32(clamp(i32(ceil(max(far_zero, near_zero))), 0, i32(consts::SUBVOXELS_PER_VOXEL_DIM * consts::VOXELS_PER_DIM)));
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
 
-    return vec2<u32>(low_point, hi_point);
-}
\ No newline at end of file
+    // inside the face
+    let denom = 1.0 / (va + vb + vc);
+    return a + ab * (vb * denom) + ac * (vc * denom);
+}
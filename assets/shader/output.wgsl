@@ -1,32 +1,49 @@
 #import sdf::consts as consts
 #import sdf::bind as bind
 #import sdf::addressing as addr
+#import sdf::parity as parity
+#import sdf::encoding as encoding
+#import sdf::output_bind as output_bind
 
 // make an sdf !
 
-@group(1) @binding(0)
-var output_texture: texture_storage_3d<r32float, read_write>;
-
 @compute @workgroup_size(8,8,8)
 fn output(@builtin(global_invocation_id) local_voxel: vec3<u32>) {
 
     let target_point = addr::voxel_local_to_local_position(vec3<i32>(local_voxel));
     let voxel_coords = addr::voxel_local_to_grid(local_voxel);
-    let source_data = textureLoad(bind::nearest_jfa, voxel_coords);
-
-    var dist = bind::cascade_info.tile_size * f32(consts::TILE_DIM_COUNT - 1u);
-    if source_data.a != 0 {
-        let local_seed_voxel = vec3<i32>(local_voxel) + source_data.xyz;
-        let subvoxel = addr::subvoxel_index_to_subvoxel(u32(source_data.a - 1));
-        let local_seed_position = addr::subvoxel_local_position(local_seed_voxel, subvoxel);
-        let dist_sq = addr::distance_squared(local_seed_position, target_point);
-        dist = sqrt(dist_sq);
-    }
+
+    let static_dist = addr::seed_distance(local_voxel, target_point, textureLoad(bind::nearest_jfa, voxel_coords));
+    let dynamic_dist = addr::seed_distance(local_voxel, target_point, textureLoad(output_bind::dynamic_nearest_jfa, voxel_coords));
+    let dist = min(static_dist, dynamic_dist);
 
     let cascade_offset = consts::VOXELS_PER_DIM * bind::cascade_info.index;
     let write_index = vec3<i32>(local_voxel + vec3<u32>(cascade_offset, 0u, 0u));
 
-    // sqrt(2) / 2 == 0.707 is the worst case overestimate of distance from the jump stitch
-    // 0.9 seems to work if we sample around the stitch point. it should be better but ¯\_(ツ)_/¯
-    textureStore(output_texture, write_index, vec4<f32>(dist * 0.9 - 0.5 * bind::cascade_info.tile_size * f32(consts::TILE_DIM_COUNT) / f32(consts::VOXELS_PER_DIM), 0.0, 0.0, 1.0));
-}
\ No newline at end of file
+    // seeds are subvoxel centres, and the surface passes somewhere within the seed subvoxel. subtracting the half
+    // diagonal of a subvoxel means we never overestimate, and are at most a full diagonal (< half a voxel) under
+    let half_diagonal = addr::SUBVOXEL_HALF_DIAGONAL * addr::subvoxel_size();
+    let lower = dist - half_diagonal;
+
+    // negative inside either layer, the union of the two is the min
+    let static_inside = parity::is_interior(voxel_coords);
+    let dynamic_inside = bind::cascade_info.signed != 0u && parity::interior_bit(textureLoad(output_bind::dynamic_interior, parity::interior_coords(voxel_coords)).r, voxel_coords);
+    let value = min(addr::apply_sign(static_dist - half_diagonal, static_inside), addr::apply_sign(dynamic_dist - half_diagonal, dynamic_inside));
+
+    if bind::cascade_info.narrow_band != 0u {
+        // keep exact distances written by refine.wgsl outside the region it is about to refine again, as long as they
+        // are still consistent with the seeds. anything else (stale values, a closer dynamic caster, or a changed
+        // sign) is replaced by the jfa estimate. the jfa can pick a seed a little further than the nearest, so the
+        // lower bound allows for the same half a voxel verify does, as well as the output format's precision.
+        // truncated values can't be checked and are replaced
+        let previous = output_bind::load_distance(write_index);
+        let magnitude = abs(previous);
+        let voxel_size = bind::cascade_info.tile_size / f32(consts::VOXELS_PER_TILE_DIM);
+        let error = encoding::encoding_error(previous, bind::cascade_info);
+        if (previous < 0.0) == (value < 0.0) && magnitude >= lower - 0.5 * voxel_size - error && magnitude <= dist + half_diagonal + error {
+            return;
+        }
+    }
+
+    output_bind::store_distance(write_index, value);
+}
@@ -0,0 +1,47 @@
+#define_import_path sdf::output_bind
+
+// the final sdf, shared by the output, refine and verify passes, and the dynamic layer's results that output and
+// refine merge into it
+
+#import sdf::bind as bind
+#import sdf::encoding as encoding
+
+#ifdef SDF_OUTPUT_R16FLOAT
+@group(1) @binding(0)
+var output_texture: texture_storage_3d<r16float, read_write>;
+#else
+#ifdef SDF_OUTPUT_R8UNORM
+@group(1) @binding(0)
+var output_texture: texture_storage_3d<r8unorm, read_write>;
+#else
+#ifdef SDF_OUTPUT_R8SNORM
+@group(1) @binding(0)
+var output_texture: texture_storage_3d<r8snorm, read_write>;
+#else
+@group(1) @binding(0)
+var output_texture: texture_storage_3d<r32float, read_write>;
+#endif
+#endif
+#endif
+
+// nearest seeds for the dynamic layer, merged with the static layer's nearest_jfa
+#ifdef SDF_NEAREST_RGBA16
+@group(1) @binding(1)
+var dynamic_nearest_jfa: texture_storage_3d<rgba16sint, read_write>;
+#else
+@group(1) @binding(1)
+var dynamic_nearest_jfa: texture_storage_3d<rgba8sint, read_write>;
+#endif
+
+// interior bits for the dynamic layer
+@group(1) @binding(2)
+var dynamic_interior: texture_storage_3d<r32uint, read_write>;
+
+// decoded distance at the given output texture coords
+fn load_distance(coords: vec3<i32>) -> f32 {
+    return encoding::decode_distance(textureLoad(output_texture, coords).r, bind::cascade_info);
+}
+
+fn store_distance(coords: vec3<i32>, dist: f32) {
+    textureStore(output_texture, coords, vec4<f32>(encoding::encode_distance(dist, bind::cascade_info), 0.0, 0.0, 1.0));
+}
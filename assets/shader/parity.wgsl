@@ -0,0 +1,138 @@
+#define_import_path sdf::parity
+
+#import sdf::consts as consts
+#import sdf::bind as bind
+#import sdf::intersect as intersect
+
+// signs come from the parity of surface crossings along columns of voxels, running along the first axis the update
+// region spans in full (so every column we touch is complete).
+//
+// bind::sign_bits holds VOXELS_PER_DIM bits per column, in order along the column, so each column is
+// VOXELS_PER_DIM / 32 consecutive words. after the voxel bits comes a bit per column.
+// - the fine raster toggles the bit of the first voxel centre past each crossing, or the column bit when there is
+//   no voxel centre past it
+// - sign.wgsl prefix-xors each column, turning the crossing bits into interior bits, and xors the total into the
+//   column bit, leaving it set for columns that cross an odd number of times (leaky)
+
+fn words_per_column() -> u32 {
+    return consts::VOXELS_PER_DIM / 32u;
+}
+
+fn column_axis() -> u32 {
+    for (var i=0u; i<3u; i++) {
+        let axis = 2u - i;
+        if bind::cascade_info.update_min[axis] == 0 && bind::cascade_info.update_max[axis] == i32(consts::TILE_DIM_COUNT) {
+            return axis;
+        }
+    }
+    // update_cascades always extends in-place updates to full z columns
+    return 2u;
+}
+
+// columns are indexed by the two coordinates across them
+fn column_index(local_voxel: vec3<u32>, axis: u32) -> u32 {
+    return local_voxel[(axis + 1u) % 3u] * consts::VOXELS_PER_DIM + local_voxel[(axis + 2u) % 3u];
+}
+
+fn column_word(column: u32, along: u32) -> u32 {
+    return column * words_per_column() + along / 32u;
+}
+
+fn column_leaky_word(column: u32) -> u32 {
+    return consts::VOXELS_PER_DIM * consts::VOXELS_PER_DIM * words_per_column() + column / 32u;
+}
+
+// interior texture coords and bit for a grid voxel (from addr::voxel_local_to_grid)
+fn interior_coords(grid_voxel: vec3<i32>) -> vec3<i32> {
+    return vec3<i32>(grid_voxel.x / 32, grid_voxel.y, grid_voxel.z);
+}
+
+fn interior_bit(word: u32, grid_voxel: vec3<i32>) -> bool {
+    return ((word >> u32(grid_voxel.x % 32)) & 1u) != 0u;
+}
+
+fn is_interior(grid_voxel: vec3<i32>) -> bool {
+    return bind::cascade_info.signed != 0u && interior_bit(textureLoad(bind::interior, interior_coords(grid_voxel)).r, grid_voxel);
+}
+
+// twice the signed area of (a, b, q). evaluated from the lexicographically smaller end, so the triangles either side
+// of a shared edge get exactly opposite values
+fn edge_function(a: vec2<f32>, b: vec2<f32>, q: vec2<f32>) -> f32 {
+    if a.x < b.x || (a.x == b.x && a.y < b.y) {
+        return (b.x - a.x) * (q.y - a.y) - (b.y - a.y) * (q.x - a.x);
+    }
+    return -((a.x - b.x) * (q.y - b.y) - (a.y - b.y) * (q.x - b.x));
+}
+
+// q is inside the edge of a counter-clockwise triangle. columns exactly on an edge belong to one side only
+// (top-left rule), so crossings at shared edges and vertices are counted exactly once
+fn edge_owns(a: vec2<f32>, b: vec2<f32>, q: vec2<f32>) -> bool {
+    let f = edge_function(a, b, q);
+    let e = b - a;
+    return f > 0.0 || (f == 0.0 && (e.y < 0.0 || (e.y == 0.0 && e.x > 0.0)));
+}
+
+// toggle the crossing bits for every column through this tile that the triangle crosses within the tile
+fn record_crossings(tri: ptr<function, intersect::TriAccel>, tile_xyz: vec3<u32>) {
+    let c = column_axis();
+    let u = (c + 1u) % 3u;
+    let w = (c + 2u) % 3u;
+    let voxel_size = bind::cascade_info.tile_size / f32(consts::VOXELS_PER_TILE_DIM);
+
+    // work in voxel units, projected across the columns
+    let v0 = (*tri).v[0] / voxel_size;
+    let v1 = (*tri).v[1] / voxel_size;
+    let v2 = (*tri).v[2] / voxel_size;
+    let p0 = vec2<f32>(v0[u], v0[w]);
+    var p1 = vec2<f32>(v1[u], v1[w]);
+    var p2 = vec2<f32>(v2[u], v2[w]);
+
+    // n[c] is twice the projected area, (c, u, w) being cyclic
+    let n = cross(v1 - v0, v2 - v0);
+    if n[c] == 0.0 {
+        // parallel to the columns
+        return;
+    }
+    if n[c] < 0.0 {
+        let tmp = p1;
+        p1 = p2;
+        p2 = tmp;
+    }
+
+    // column centres are at voxel + 0.5
+    let tile_min = tile_xyz * consts::VOXELS_PER_TILE_DIM;
+    let p_min = min(p0, min(p1, p2));
+    let p_max = max(p0, max(p1, p2));
+    let start = vec2<u32>(max(vec2<f32>(f32(tile_min[u]), f32(tile_min[w])), ceil(p_min - 0.5)));
+    let end = vec2<u32>(min(vec2<f32>(f32(tile_min[u] + consts::VOXELS_PER_TILE_DIM), f32(tile_min[w] + consts::VOXELS_PER_TILE_DIM)), floor(p_max - 0.5) + 1.0));
+
+    for (var i=start.x; i<end.x; i++) {
+        for (var j=start.y; j<end.y; j++) {
+            let q = vec2<f32>(f32(i), f32(j)) + 0.5;
+            if !(edge_owns(p0, p1, q) && edge_owns(p1, p2, q) && edge_owns(p2, p0, q)) {
+                continue;
+            }
+
+            // crossing position along the column. the tile containing it records it, so triangles that span
+            // several tiles along the column are only counted once
+            let t = v0[c] - (n[u] * (q.x - v0[u]) + n[w] * (q.y - v0[w])) / n[c];
+            let owner = floor(t);
+            if owner < f32(tile_min[c]) || owner >= f32(tile_min[c] + consts::VOXELS_PER_TILE_DIM) {
+                continue;
+            }
+
+            var local_voxel = vec3<u32>(0u);
+            local_voxel[u] = i;
+            local_voxel[w] = j;
+            let column = column_index(local_voxel, c);
+
+            // first voxel centre past the crossing
+            let along = u32(floor(t + 0.5));
+            if along < consts::VOXELS_PER_DIM {
+                atomicXor(&bind::sign_bits[column_word(column, along)], 1u << (along % 32u));
+            } else {
+                atomicXor(&bind::sign_bits[column_leaky_word(column)], 1u << (column % 32u));
+            }
+        }
+    }
+}
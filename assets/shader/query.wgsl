@@ -0,0 +1,86 @@
+// batched point and ray queries against the output texture, see SdfQueries
+// samples and traces through sdf::sample, with the sdf bound at group 1
+
+#import sdf::sample as sampling
+
+struct Query {
+    // xyz = point or ray origin, w = 0 for a point, 1 for a ray
+    origin: vec4<f32>,
+    // xyz = normalized ray direction, w = max distance along the ray
+    direction: vec4<f32>,
+}
+
+struct Queries {
+    count: u32,
+    // rays stop when the distance drops below this
+    hit_threshold: f32,
+    max_steps: u32,
+    queries: array<Query>,
+}
+
+struct QueryResult {
+    // xyz = query point or ray hit point, w = distance at the point, or travelled along the ray
+    point: vec4<f32>,
+    // xyz = normalized gradient at the point, zero where it vanishes
+    normal: vec4<f32>,
+    // QUERY_*
+    flags: u32,
+    steps: u32,
+}
+
+// the point (or ray origin) is within a cascade
+let QUERY_COVERED: u32 = 1u;
+// the ray reached a surface
+let QUERY_HIT: u32 = 2u;
+
+@group(0) @binding(0)
+var<storage> queries: Queries;
+
+@group(0) @binding(1)
+var<storage, read_write> results: array<QueryResult>;
+
+fn query_point(pos: vec3<f32>) -> QueryResult {
+    var res: QueryResult;
+    res.point = vec4<f32>(pos, 0.0);
+
+    let probe = sampling::sample_distance(pos);
+    if probe.cascade == sampling::cascade_count() {
+        return res;
+    }
+
+    res.point.w = probe.distance;
+    res.normal = vec4<f32>(sampling::sample_normal(pos, probe.cascade), 0.0);
+    res.flags = QUERY_COVERED;
+    return res;
+}
+
+fn query_ray(origin: vec3<f32>, direction: vec3<f32>, max_distance: f32) -> QueryResult {
+    var res: QueryResult;
+    if sampling::find_cascade(origin) != sampling::cascade_count() {
+        res.flags = QUERY_COVERED;
+    }
+
+    let trace = sampling::sphere_trace(origin, direction, max_distance, queries.hit_threshold, queries.max_steps);
+    if trace.hit {
+        res.flags |= QUERY_HIT;
+        res.normal = vec4<f32>(sampling::sample_normal(trace.position, trace.cascade), 0.0);
+    }
+    res.point = vec4<f32>(trace.position, trace.distance);
+    res.steps = trace.steps;
+    return res;
+}
+
+@compute @workgroup_size(64,1,1)
+fn query(@builtin(global_invocation_id) g_id: vec3<u32>) {
+    let index = g_id.x;
+    if index >= queries.count {
+        return;
+    }
+
+    let q = queries.queries[index];
+    if q.origin.w == 0.0 {
+        results[index] = query_point(q.origin.xyz);
+    } else {
+        results[index] = query_ray(q.origin.xyz, q.direction.xyz, q.direction.w);
+    }
+}
@@ -0,0 +1,88 @@
+// replace the jfa estimate with exact triangle distances for voxels near a surface, enabled with
+// SceneSdfSettings::narrow_band
+// runs over a static update's region right after output.wgsl has written the cascade, while the update's coarse tile
+// lists are still live, and overwrites the estimate within the band. later outputs keep these values for as long as
+// they agree with the seeds
+
+#import sdf::consts as consts
+#import sdf::bind as bind
+#import sdf::addressing as addr
+#import sdf::intersect as intersect
+#import sdf::parity as parity
+#import sdf::output_bind as output_bind
+
+// written by coarse.wgsl
+struct CoarseBins {
+    counts: array<u32, consts::TILE_COUNT>,
+    write_index: array<u32, consts::TILE_COUNT>,
+    offsets: array<u32, consts::TILE_COUNT>,
+}
+
+@group(2) @binding(1)
+var<storage, read_write> bins: CoarseBins;
+
+@compute @workgroup_size(8,8,8)
+fn refine(@builtin(global_invocation_id) g_id: vec3<u32>) {
+    let local_voxel = vec3<i32>(g_id);
+    let local_tile = local_voxel / i32(consts::VOXELS_PER_TILE_DIM);
+    if !all(local_tile >= bind::cascade_info.update_min.xyz && local_tile < bind::cascade_info.update_max.xyz) {
+        return;
+    }
+
+    let target_point = addr::voxel_local_to_local_position(local_voxel);
+    let voxel_coords = addr::voxel_local_to_grid(g_id);
+    let seed_dist = addr::seed_distance(g_id, target_point, textureLoad(bind::nearest_jfa, voxel_coords));
+    let dynamic_dist = addr::seed_distance(g_id, target_point, textureLoad(output_bind::dynamic_nearest_jfa, voxel_coords));
+    let half_diagonal = addr::SUBVOXEL_HALF_DIAGONAL * addr::subvoxel_size();
+
+    // outside the band we keep output.wgsl's estimate
+    let voxel_size = bind::cascade_info.tile_size / f32(consts::VOXELS_PER_TILE_DIM);
+    if seed_dist - half_diagonal > f32(bind::cascade_info.narrow_band) * voxel_size {
+        return;
+    }
+
+    // the nearest triangle is no further than the seed plus half a subvoxel, so it is in one of the tiles that overlap
+    // that radius. tiles outside the update region only list triangles from this update, so near the region's edge
+    // we can come out too far, but never beyond that radius
+    let upper = seed_dist + half_diagonal;
+    let tile_min = vec3<u32>(clamp(vec3<i32>(floor((target_point - upper) / bind::cascade_info.tile_size)), vec3<i32>(0), vec3<i32>(i32(consts::TILE_DIM_COUNT - 1u))));
+    let tile_max = vec3<u32>(clamp(vec3<i32>(floor((target_point + upper) / bind::cascade_info.tile_size)), vec3<i32>(0), vec3<i32>(i32(consts::TILE_DIM_COUNT - 1u))));
+
+    var best_dist_sq = upper * upper;
+    for (var x=tile_min.x; x<=tile_max.x; x++) {
+        for (var y=tile_min.y; y<=tile_max.y; y++) {
+            for (var z=tile_min.z; z<=tile_max.z; z++) {
+                let tile = (((z * consts::TILE_DIM_COUNT) + y) * consts::TILE_DIM_COUNT) + x;
+                let start = bins.offsets[tile];
+                let end = min(start + bins.counts[tile], consts::MAX_ID_COUNT);
+
+                for (var i=start; i<end; i++) {
+                    let tri = bind::transformed_tris[bind::coarse_ids[i]];
+                    let closest = intersect::closest_point_on_tri(
+                        target_point,
+                        tri.vertex_positions[0].xyz,
+                        tri.vertex_positions[1].xyz,
+                        tri.vertex_positions[2].xyz,
+                    );
+                    // measured to the slab for triangles with a minimum thickness, like the seeds
+                    let dist = max(sqrt(addr::distance_squared(closest, target_point)) - tri.half_thickness, 0.0);
+                    best_dist_sq = min(best_dist_sq, dist * dist);
+                }
+            }
+        }
+    }
+
+    // only a closer dynamic caster puts the merged seeds nearer than that, and output.wgsl's estimate covers it
+    let dist = sqrt(best_dist_sq);
+    if dist > min(seed_dist, dynamic_dist) + half_diagonal {
+        return;
+    }
+
+    // merged with the dynamic layer like output.wgsl
+    let static_inside = parity::is_interior(voxel_coords);
+    let dynamic_inside = bind::cascade_info.signed != 0u && parity::interior_bit(textureLoad(output_bind::dynamic_interior, parity::interior_coords(voxel_coords)).r, voxel_coords);
+    let value = min(addr::apply_sign(dist, static_inside), addr::apply_sign(dynamic_dist - half_diagonal, dynamic_inside));
+
+    let cascade_offset = consts::VOXELS_PER_DIM * bind::cascade_info.index;
+    output_bind::store_distance(local_voxel + vec3<i32>(i32(cascade_offset), 0, 0), value);
+}
@@ -0,0 +1,216 @@
+#define_import_path sdf::sample
+
+// reading the scene sdf from other shaders (materials, post processing, compute).
+// bind SdfSampleBindGroup::layout at group 1, or at group 3 with the SDF_SAMPLE_GROUP_3 shader def (after a mesh
+// pipeline's view / material / mesh groups), and set SdfSampleBindGroup::bind_group or use SetSdfSampleBindGroup.
+// that layout is for fragment and compute shaders. vertex shaders need SdfSampleBindGroup::vertex_layout and
+// SetSdfSampleBindGroup<I, true>, which need DownlevelFlags::VERTEX_STORAGE.
+// texels are loaded and filtered here rather than with a sampler, so this works for every output format. distances
+// are in world units, clamped to each cascade's truncation band when it has one
+
+#import sdf::consts as consts
+#import sdf::types as types
+#import sdf::encoding as encoding
+
+#ifdef SDF_SAMPLE_GROUP_3
+@group(3) @binding(0)
+var<storage> cascades_info: types::CascadeInfos;
+
+@group(3) @binding(1)
+var sdf_texture: texture_3d<f32>;
+#else
+@group(1) @binding(0)
+var<storage> cascades_info: types::CascadeInfos;
+
+@group(1) @binding(1)
+var sdf_texture: texture_3d<f32>;
+#endif
+
+// distances blend into the next coarser cascade over this many voxels from a cascade's border
+let BLEND_VOXELS: f32 = 2.0;
+
+struct DistanceSample {
+    // 0 when cascade == cascade_count()
+    distance: f32,
+    // finest cascade covering the point, cascade_count() if there is none
+    cascade: u32,
+}
+
+struct Trace {
+    // where the ray hit or stopped
+    position: vec3<f32>,
+    // travelled along the ray
+    distance: f32,
+    steps: u32,
+    // finest cascade covering the final position, cascade_count() if the ray left the sdf
+    cascade: u32,
+    hit: bool,
+}
+
+fn cascade_count() -> u32 {
+    return cascades_info.count;
+}
+
+fn voxel_size(cascade: u32) -> f32 {
+    return cascades_info.cascades[cascade].tile_size / f32(consts::VOXELS_PER_TILE_DIM);
+}
+
+// position in voxels from the cascade's first voxel centre
+fn cascade_texel(pos: vec3<f32>, cascade: u32) -> vec3<f32> {
+    let info = cascades_info.cascades[cascade];
+    let cascade_min = vec3<f32>(info.origin.xyz) * info.tile_size;
+    return (pos - cascade_min) / voxel_size(cascade) - 0.5;
+}
+
+// the point is between the cascade's outer voxel centres, where it can be filtered
+fn in_cascade(pos: vec3<f32>, cascade: u32) -> bool {
+    let texel = cascade_texel(pos, cascade);
+    return all(texel >= vec3<f32>(0.0)) && all(texel <= vec3<f32>(f32(consts::VOXELS_PER_DIM - 1u)));
+}
+
+// finest cascade covering the point, cascade_count() if there is none
+fn find_cascade(pos: vec3<f32>) -> u32 {
+    for (var cascade=0u; cascade<cascades_info.count; cascade++) {
+        if in_cascade(pos, cascade) {
+            return cascade;
+        }
+    }
+    return cascades_info.count;
+}
+
+// raw texture value, clamped to the cascade
+fn load_texel(voxel: vec3<i32>, cascade: u32) -> f32 {
+    let coords = clamp(voxel, vec3<i32>(0), vec3<i32>(i32(consts::VOXELS_PER_DIM - 1u))) + vec3<i32>(i32(cascade * consts::VOXELS_PER_DIM), 0, 0);
+    return textureLoad(sdf_texture, coords, 0).r;
+}
+
+// trilinear between the cascade's voxel centres
+fn sample_cascade(pos: vec3<f32>, cascade: u32) -> f32 {
+    let texel = cascade_texel(pos, cascade);
+    let base = vec3<i32>(floor(texel));
+    let t = texel - floor(texel);
+
+    let c00 = mix(load_texel(base, cascade), load_texel(base + vec3<i32>(1, 0, 0), cascade), t.x);
+    let c10 = mix(load_texel(base + vec3<i32>(0, 1, 0), cascade), load_texel(base + vec3<i32>(1, 1, 0), cascade), t.x);
+    let c01 = mix(load_texel(base + vec3<i32>(0, 0, 1), cascade), load_texel(base + vec3<i32>(1, 0, 1), cascade), t.x);
+    let c11 = mix(load_texel(base + vec3<i32>(0, 1, 1), cascade), load_texel(base + vec3<i32>(1, 1, 1), cascade), t.x);
+    let value = mix(mix(c00, c10, t.y), mix(c01, c11, t.y), t.z);
+    return encoding::decode_distance(value, cascades_info.cascades[cascade]);
+}
+
+// filtered distance from the finest covering cascade, blended into the next one near its border so there is no seam
+fn sample_distance(pos: vec3<f32>) -> DistanceSample {
+    var res: DistanceSample;
+    res.cascade = find_cascade(pos);
+    if res.cascade == cascades_info.count {
+        return res;
+    }
+
+    res.distance = sample_cascade(pos, res.cascade);
+
+    let coarser = res.cascade + 1u;
+    if coarser < cascades_info.count && in_cascade(pos, coarser) {
+        let texel = cascade_texel(pos, res.cascade);
+        let edge = min(texel, vec3<f32>(f32(consts::VOXELS_PER_DIM - 1u)) - texel);
+        let weight = clamp(min(edge.x, min(edge.y, edge.z)) / BLEND_VOXELS, 0.0, 1.0);
+        if weight < 1.0 {
+            res.distance = mix(sample_cascade(pos, coarser), res.distance, weight);
+        }
+    }
+
+    return res;
+}
+
+// central differences over a voxel of the cascade, roughly unit length away from surfaces
+fn sample_gradient(pos: vec3<f32>, cascade: u32) -> vec3<f32> {
+    let h = voxel_size(cascade);
+    return vec3<f32>(
+        sample_cascade(pos + vec3<f32>(h, 0.0, 0.0), cascade) - sample_cascade(pos - vec3<f32>(h, 0.0, 0.0), cascade),
+        sample_cascade(pos + vec3<f32>(0.0, h, 0.0), cascade) - sample_cascade(pos - vec3<f32>(0.0, h, 0.0), cascade),
+        sample_cascade(pos + vec3<f32>(0.0, 0.0, h), cascade) - sample_cascade(pos - vec3<f32>(0.0, 0.0, h), cascade),
+    ) / (2.0 * h);
+}
+
+// normalized gradient, zero where it vanishes
+fn sample_normal(pos: vec3<f32>, cascade: u32) -> vec3<f32> {
+    let gradient = sample_gradient(pos, cascade);
+    let len = length(gradient);
+    if len < 1e-20 {
+        return vec3<f32>(0.0);
+    }
+    return gradient / len;
+}
+
+// sphere trace along a normalized direction until the distance drops below hit_threshold, the ray leaves the sdf, or
+// it has travelled max_distance. steps are at least half a voxel so rays can't stall at grazing angles, and the
+// cascade is re-picked every step so rays move between cascades in both directions
+fn sphere_trace(origin: vec3<f32>, direction: vec3<f32>, max_distance: f32, hit_threshold: f32, max_steps: u32) -> Trace {
+    var res: Trace;
+
+    var t = 0.0;
+    for (res.steps = 0u; res.steps < max_steps; res.steps++) {
+        let probe = sample_distance(origin + direction * t);
+        res.cascade = probe.cascade;
+        if probe.cascade == cascades_info.count {
+            break;
+        }
+
+        if probe.distance < hit_threshold {
+            res.hit = true;
+            break;
+        }
+
+        let advance = max(probe.distance, 0.5 * voxel_size(probe.cascade));
+        if t + advance > max_distance {
+            t = max_distance;
+            break;
+        }
+        t += advance;
+    }
+
+    res.position = origin + direction * t;
+    res.distance = t;
+    return res;
+}
+
+// light reaching origin from along a normalized direction, 0 in full shadow to 1 unoccluded, with a penumbra from the
+// closest miss (larger hardness = sharper). start a voxel or so off the surface so the ray doesn't hit it immediately.
+// space outside the sdf counts as empty
+fn soft_shadow(origin: vec3<f32>, direction: vec3<f32>, max_distance: f32, hardness: f32) -> f32 {
+    var light = 1.0;
+    var t = 0.0;
+    for (var steps=0u; steps<128u && t < max_distance; steps++) {
+        let probe = sample_distance(origin + direction * t);
+        if probe.cascade == cascades_info.count {
+            break;
+        }
+        if probe.distance <= 0.0 {
+            return 0.0;
+        }
+
+        light = min(light, hardness * probe.distance / max(t, 1e-4));
+        t += max(probe.distance, 0.5 * voxel_size(probe.cascade));
+    }
+    return clamp(light, 0.0, 1.0);
+}
+
+// ambient occlusion from sample_count distances taken along the normal at multiples of spacing, 0 fully occluded to
+// 1 open. nearer samples weigh more
+fn ambient_occlusion(pos: vec3<f32>, normal: vec3<f32>, spacing: f32, sample_count: u32) -> f32 {
+    var occlusion = 0.0;
+    var weight = 1.0;
+    var total = 0.0;
+    for (var i=1u; i<=sample_count; i++) {
+        let h = spacing * f32(i);
+        let probe = sample_distance(pos + normal * h);
+        if probe.cascade != cascades_info.count {
+            occlusion += weight * clamp((h - probe.distance) / h, 0.0, 1.0);
+        }
+        total += weight;
+        weight *= 0.5;
+    }
+    if total == 0.0 {
+        return 1.0;
+    }
+    return 1.0 - occlusion / total;
+}
@@ -0,0 +1,126 @@
+// turn the crossings recorded by the fine raster into interior bits, see parity.wgsl
+
+#import sdf::consts as consts
+#import sdf::bind as bind
+#import sdf::addressing as addr
+#import sdf::parity as parity
+
+@compute @workgroup_size(256,1,1)
+fn sign_clear(@builtin(global_invocation_id) g_id: vec3<u32>) {
+    if g_id.x < consts::SIGN_WORD_COUNT {
+        atomicStore(&bind::sign_bits[g_id.x], 0u);
+    }
+}
+
+// column is within the update region across its axis
+fn column_in_region(u: i32, w: i32, axis: u32) -> bool {
+    let region_min = bind::cascade_info.update_min.xyz * i32(consts::VOXELS_PER_TILE_DIM);
+    let region_max = bind::cascade_info.update_max.xyz * i32(consts::VOXELS_PER_TILE_DIM);
+    let a = (axis + 1u) % 3u;
+    let b = (axis + 2u) % 3u;
+    return u >= region_min[a] && u < region_max[a] && w >= region_min[b] && w < region_max[b];
+}
+
+fn column_leaky(column: u32) -> bool {
+    return (atomicLoad(&bind::sign_bits[parity::column_leaky_word(column)]) & (1u << (column % 32u))) != 0u;
+}
+
+fn column_interior(column: u32, along: u32) -> bool {
+    return (atomicLoad(&bind::sign_bits[parity::column_word(column, along)]) & (1u << (along % 32u))) != 0u;
+}
+
+// a thread per column
+@compute @workgroup_size(64,1,1)
+fn sign_scan(@builtin(global_invocation_id) g_id: vec3<u32>) {
+    let column = g_id.x;
+    let axis = parity::column_axis();
+    if !column_in_region(i32(column / consts::VOXELS_PER_DIM), i32(column % consts::VOXELS_PER_DIM), axis) {
+        return;
+    }
+
+    // prefix xor within each word, inverted when the words before it crossed an odd number of times
+    var carry = 0u;
+    for (var i=0u; i<parity::words_per_column(); i++) {
+        let index = parity::column_word(column, i * 32u);
+        var x = atomicLoad(&bind::sign_bits[index]);
+        x ^= x << 1u;
+        x ^= x << 2u;
+        x ^= x << 4u;
+        x ^= x << 8u;
+        x ^= x << 16u;
+        if carry != 0u {
+            x = ~x;
+        }
+        carry = x >> 31u;
+        atomicStore(&bind::sign_bits[index], x);
+    }
+
+    // the column bit holds crossings past the last voxel centre, add the rest
+    if carry != 0u {
+        atomicXor(&bind::sign_bits[parity::column_leaky_word(column)], 1u << (column % 32u));
+    }
+}
+
+// a thread per packed interior word, writing the bits for voxels in the update region
+@compute @workgroup_size(4,8,8)
+fn sign_resolve(@builtin(global_invocation_id) g_id: vec3<u32>) {
+    if g_id.x >= consts::VOXELS_PER_DIM / 32u {
+        return;
+    }
+
+    let axis = parity::column_axis();
+    let u = (axis + 1u) % 3u;
+    let w = (axis + 2u) % 3u;
+    let tolerance = i32(bind::cascade_info.leak_tolerance);
+
+    let coords = vec3<i32>(vec3<u32>(g_id.x + consts::VOXELS_PER_DIM / 32u * bind::cascade_info.index, g_id.yz));
+    var word = textureLoad(bind::interior, coords).r;
+
+    for (var bit=0u; bit<32u; bit++) {
+        let local_voxel = addr::voxel_grid_to_local(vec3<u32>(g_id.x * 32u + bit, g_id.yz));
+        let local_tile = vec3<i32>(local_voxel / consts::VOXELS_PER_TILE_DIM);
+        if !all(local_tile >= bind::cascade_info.update_min.xyz && local_tile < bind::cascade_info.update_max.xyz) {
+            continue;
+        }
+
+        let along = local_voxel[axis];
+        var interior = false;
+        let column = parity::column_index(local_voxel, axis);
+        if !column_leaky(column) {
+            interior = column_interior(column, along);
+        } else {
+            // borrow from the nearest watertight column, by rings of increasing size
+            var found = false;
+            for (var r=1; r<=tolerance && !found; r++) {
+                for (var du=-r; du<=r && !found; du++) {
+                    for (var dw=-r; dw<=r && !found; dw++) {
+                        if max(abs(du), abs(dw)) != r {
+                            continue;
+                        }
+
+                        let nu = i32(local_voxel[u]) + du;
+                        let nw = i32(local_voxel[w]) + dw;
+                        if !column_in_region(nu, nw, axis) {
+                            continue;
+                        }
+
+                        let neighbour = u32(nu) * consts::VOXELS_PER_DIM + u32(nw);
+                        if !column_leaky(neighbour) {
+                            found = true;
+                            interior = column_interior(neighbour, along);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mask = 1u << bit;
+        if interior {
+            word |= mask;
+        } else {
+            word &= ~mask;
+        }
+    }
+
+    textureStore(bind::interior, coords, vec4<u32>(word, 0u, 0u, 0u));
+}
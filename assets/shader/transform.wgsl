@@ -0,0 +1,64 @@
+// move each triangle of the update's mesh instances into cascade space, once per update
+// coarse.wgsl and fine.wgsl read the results from bind::transformed_tris
+
+#import sdf::consts as consts
+#import sdf::bind as bind
+#import sdf::types as types
+
+// mesh-space position, or 0-1 within the mesh aabb for quantized formats (the transform includes the dequantization)
+fn load_vertex(instance: types::MeshInstance, local_index_id: u32) -> vec4<f32> {
+    let vertex = bind::indices[instance.index_offset + local_index_id];
+
+    if instance.vertex_format == consts::VERTEX_FORMAT_UNORM16 {
+        let base = instance.vertex_offset + vertex * 2u;
+        let xy = unpack2x16unorm(bind::vertices[base]);
+        let z = unpack2x16unorm(bind::vertices[base + 1u]).x;
+        return vec4<f32>(xy, z, 1.0);
+    }
+
+    let base = instance.vertex_offset + vertex * 3u;
+    return vec4<f32>(
+        bitcast<f32>(bind::vertices[base]),
+        bitcast<f32>(bind::vertices[base + 1u]),
+        bitcast<f32>(bind::vertices[base + 2u]),
+        1.0
+    );
+}
+
+// last instance with tri_offset <= tri_id
+fn find_instance(tri_id: u32) -> u32 {
+    var low = 0u;
+    var high = bind::mesh_headers.mesh_count;
+    while high - low > 1u {
+        let mid = (low + high) / 2u;
+        if bind::mesh_headers.instances[mid].tri_offset <= tri_id {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    return low;
+}
+
+let TRANSFORM_THREADS = 64u; // must match workgroup_size.x
+@compute @workgroup_size(64, 1, 1)
+fn transform_tris(@builtin(global_invocation_id) g_id: vec3<u32>, @builtin(num_workgroups) num_workgroups: vec3<u32>) {
+    // dispatch is split over y for large triangle counts
+    let tri_id = g_id.y * num_workgroups.x * TRANSFORM_THREADS + g_id.x;
+    if tri_id >= bind::mesh_headers.tri_count {
+        return;
+    }
+
+    let instance = bind::mesh_headers.instances[find_instance(tri_id)];
+    let transform = bind::transforms.t[instance.transform_index];
+    let origin = vec4<f32>(bind::cascade_info.origin) * bind::cascade_info.tile_size;
+    let local_index_id = (tri_id - instance.tri_offset) * 3u;
+
+    var transformed: types::TransformedTri;
+    transformed.vertex_positions[0] = transform * load_vertex(instance, local_index_id) - origin;
+    transformed.vertex_positions[1] = transform * load_vertex(instance, local_index_id + 1u) - origin;
+    transformed.vertex_positions[2] = transform * load_vertex(instance, local_index_id + 2u) - origin;
+    transformed.half_thickness = instance.half_thickness;
+
+    bind::transformed_tris[tri_id] = transformed;
+}
@@ -1,10 +1,23 @@
 #define_import_path sdf::types
 
+struct MeshInstance {
+    transform_index: u32,
+    // offsets into bind::indices and bind::vertices
+    index_offset: u32,
+    index_count: u32,
+    vertex_offset: u32,
+    // consts::VERTEX_FORMAT_*
+    vertex_format: u32,
+    // first triangle of this instance in the update's triangle list
+    tri_offset: u32,
+    // half the entity's SdfMinThickness
+    half_thickness: f32,
+}
+
 struct MeshHeader {
     mesh_count: u32,
     tri_count: u32,
-    // index, offset, count, unused
-    index_offset_count: array<vec4<u32>>, // len = mesh_count
+    instances: array<MeshInstance>, // len = mesh_count
 }
 
 struct Transforms {
@@ -14,8 +27,21 @@ struct Transforms {
 struct CascadeInfo {
     origin: vec4<i32>,
     redraw: vec4<i32>,
+    // local tile region being revoxelized, [min, max)
+    update_min: vec4<i32>,
+    update_max: vec4<i32>,
     tile_size: f32,
     index: u32,
+    // voxels from a surface to refine with exact triangle distances, 0 if disabled
+    narrow_band: u32,
+    // 1 to count surface crossings and write the interior texture, see parity.wgsl
+    signed: u32,
+    // columns to search for a watertight neighbour when a column leaks
+    leak_tolerance: u32,
+    // consts::VOXELIZATION_*
+    voxelization: u32,
+    // output distances are clamped to +/- this many voxels, 0 for unclamped. see encoding.wgsl
+    truncation: u32,
 }
 
 struct CascadeInfos {
@@ -23,6 +49,8 @@ struct CascadeInfos {
     cascades: array<CascadeInfo>,
 }
 
-struct ProcessedTri {
+struct TransformedTri {
     vertex_positions: array<vec4<f32>, 3>,
+    // the triangle is voxelized as a slab this far either side of its plane
+    half_thickness: f32,
 }
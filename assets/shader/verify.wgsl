@@ -0,0 +1,106 @@
+// brute force check of the output distances, enabled with SceneSdfSettings::verify
+// for one voxel per tile we scan every seed subvoxel around it and compare the distance to the nearest against
+// what the jfa + stitch produced. seeds outside the cascade are ignored, so only overestimates are reported.
+// signed outputs are compared by magnitude. truncated outputs only ever underestimate, so are never reported
+
+#import sdf::consts as consts
+#import sdf::bind as bind
+#import sdf::addressing as addr
+#import sdf::encoding as encoding
+#import sdf::output_bind as output_bind
+
+struct VerifyResults {
+    // worst overestimate in voxels, as f32 bits (positive floats order the same as their bits)
+    max_error: atomic<u32>,
+    // samples over the tolerance
+    failures: atomic<u32>,
+    samples: atomic<u32>,
+    padding: u32,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> results: VerifyResults;
+
+// max voxels scanned in each direction from the sample
+let VERIFY_RADIUS: i32 = 12;
+// allowed overestimate in voxels
+let VERIFY_TOLERANCE: f32 = 0.5;
+
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+@compute @workgroup_size(64,1,1)
+fn verify(@builtin(global_invocation_id) g_id: vec3<u32>) {
+    let tile = g_id.x;
+    if tile >= consts::TILE_COUNT {
+        return;
+    }
+
+    // pick a different voxel whenever the cascade moves
+    let origin = vec3<u32>(bind::cascade_info.origin.xyz);
+    let voxel_index = hash(tile ^ hash(origin.x ^ hash(origin.y ^ hash(origin.z)))) % consts::VOXELS_PER_TILE;
+    let tile_voxel = vec3<u32>(
+        voxel_index % consts::VOXELS_PER_TILE_DIM,
+        (voxel_index / consts::VOXELS_PER_TILE_DIM) % consts::VOXELS_PER_TILE_DIM,
+        voxel_index / (consts::VOXELS_PER_TILE_DIM * consts::VOXELS_PER_TILE_DIM),
+    );
+    let local_voxel = vec3<i32>(addr::tile_index_to_local(tile) * consts::VOXELS_PER_TILE_DIM + tile_voxel);
+    let target_point = addr::voxel_local_to_local_position(local_voxel);
+
+    let cascade_offset = consts::VOXELS_PER_DIM * bind::cascade_info.index;
+    let output_dist = abs(output_bind::load_distance(local_voxel + vec3<i32>(i32(cascade_offset), 0, 0)));
+
+    let voxel_size = bind::cascade_info.tile_size / f32(consts::VOXELS_PER_TILE_DIM);
+    let half_diagonal = addr::SUBVOXEL_HALF_DIAGONAL * addr::subvoxel_size();
+
+    // any seed closer than the output distance is within this many voxels
+    let radius = min(i32(ceil((output_dist + half_diagonal) / voxel_size)) + 1, VERIFY_RADIUS);
+
+    var found = false;
+    var best_dist_sq = 0.0;
+    for (var x=-radius; x<=radius; x++) {
+        for (var y=-radius; y<=radius; y++) {
+            for (var z=-radius; z<=radius; z++) {
+                let local_seed_voxel = local_voxel + vec3<i32>(x, y, z);
+                let seed_coords = addr::voxel_local_to_maybe_grid(local_seed_voxel);
+                if seed_coords.x == -1 {
+                    continue;
+                }
+
+                let seed_value = addr::load_seed(seed_coords);
+                if all(seed_value == vec4<u32>(0u)) {
+                    continue;
+                }
+
+                for (var shift=0u; shift<consts::SUBVOXELS_PER_VOXEL_DIM * consts::SUBVOXELS_PER_VOXEL_DIM * consts::SUBVOXELS_PER_VOXEL_DIM; shift++) {
+                    if addr::seed_has_subvoxel(seed_value, shift) {
+                        let local_seed_position = addr::subvoxel_local_position(local_seed_voxel, addr::subvoxel_index_to_subvoxel(shift));
+                        let dist_sq = addr::distance_squared(local_seed_position, target_point);
+                        if !found || dist_sq < best_dist_sq {
+                            found = true;
+                            best_dist_sq = dist_sq;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    atomicAdd(&results.samples, 1u);
+    if !found {
+        return;
+    }
+
+    // the output format's precision isn't counted
+    let quantization = encoding::encoding_error(output_dist, bind::cascade_info);
+    let error = (output_dist - quantization - (sqrt(best_dist_sq) - half_diagonal)) / voxel_size;
+    if error > 0.0 {
+        atomicMax(&results.max_error, bitcast<u32>(error));
+    }
+    if error > VERIFY_TOLERANCE {
+        atomicAdd(&results.failures, 1u);
+    }
+}
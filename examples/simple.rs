@@ -46,6 +46,7 @@ fn main() {
         .add_startup_system(setup)
         .add_system(camera_controller)
         .add_system(toggle)
+        .add_system(orbit)
         .run()
 }
 
@@ -119,6 +120,17 @@ fn setup(
             }
         });
 
+    // a moving cube, revoxelized into the dynamic layer
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(shape::Cube::new(1.0).into()),
+        material: mat.clone(),
+        transform: Transform::from_xyz(0.0, 0.0, 3.0),
+        ..Default::default()
+    })
+    .insert(Name::new("orbiter"))
+    .insert(SdfDynamic)
+    .insert(Orbit);
+
     // Light
     commands.spawn(PointLightBundle {
         transform: Transform::from_xyz(0.0, 2.0, 0.0),
@@ -192,6 +204,20 @@ impl Default for CameraController {
 #[derive(Component)]
 struct Cube;
 
+#[derive(Component)]
+struct Orbit;
+
+fn orbit(
+    time: Res<Time>,
+    mut q: Query<&mut Transform, With<Orbit>>,
+) {
+    let angle = time.elapsed_seconds() * 0.5;
+    for mut transform in &mut q {
+        transform.translation = Vec3::new(angle.sin(), 0.0, angle.cos()) * 3.0;
+        transform.rotation = Quat::from_rotation_y(angle * 2.0);
+    }
+}
+
 fn toggle(
     mut commands: Commands,
     mut centre: ResMut<SdfCentre>,
@@ -199,6 +225,7 @@ fn toggle(
     mut q: Query<(Entity, Option<&DebugSdf>, &GlobalTransform, &mut Transform), With<Camera3d>>,
     mut base: Query<&mut Visibility, With<Handle<Scene>>>,
     mut state: ResMut<SceneSdfSettings>,
+    mut rebuild: EventWriter<SdfRebuild>,
     mut still: Local<bool>,
     #[allow(unused_variables)]
     frame: Res<FrameCount>,
@@ -223,11 +250,7 @@ fn toggle(
     }
 
     if key_input.just_pressed(KeyCode::Key8) {
-        for cascade in &mut state.cascades {
-            let f = cascade.far_distance;
-            cascade.far_distance = 1.0;
-            cascade.far_distance = f;
-        }
+        rebuild.send(SdfRebuild::All);
     }
 
     if key_input.just_pressed(KeyCode::Key9) {
@@ -242,6 +265,10 @@ fn toggle(
         }
     }
 
+    if key_input.just_pressed(KeyCode::V) {
+        state.verify = !state.verify;
+    }
+
     if key_input.just_pressed(KeyCode::F) {
         *still = !*still;
     }
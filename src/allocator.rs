@@ -0,0 +1,350 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use bevy::{
+    core::{cast_slice, Pod, Zeroable},
+    prelude::*,
+    render::{
+        render_resource::{BindingResource, Buffer, BufferDescriptor, BufferUsages},
+        renderer::{RenderDevice, RenderQueue},
+    },
+    utils::HashMap,
+};
+
+// min elements for a new gpu buffer, so we don't reallocate repeatedly while a scene is streaming in
+const MIN_CAPACITY: usize = 1 << 16;
+
+// max elements moved per frame when defragmenting
+const DEFRAG_BUDGET: usize = 1 << 16;
+
+// start defragmenting when free space exceeds 1 / DEFRAG_THRESHOLD of the used range
+const DEFRAG_THRESHOLD: usize = 4;
+
+// sub-allocates per-mesh ranges of a single gpu storage buffer.
+// a cpu mirror is kept so that only changed ranges need to be written each frame, and so that allocations
+// can be moved to fill holes without reading back from the gpu.
+// offsets returned by `get` are only valid until the next `defragment` call
+pub struct MeshAllocator<T: Pod> {
+    label: &'static str,
+    data: Vec<T>,
+    allocations: HashMap<Handle<Mesh>, Range<usize>>,
+    // allocation start -> handle, for finding what sits after a hole
+    by_start: BTreeMap<usize, Handle<Mesh>>,
+    // sorted, coalesced free ranges within data
+    free: Vec<Range<usize>>,
+    // ranges that need writing to the gpu
+    dirty: Vec<Range<usize>>,
+    buffer: Option<(Buffer, usize)>,
+    defragmenting: bool,
+}
+
+impl<T: Pod> MeshAllocator<T> {
+    pub fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            data: Default::default(),
+            allocations: Default::default(),
+            by_start: Default::default(),
+            free: Default::default(),
+            dirty: Default::default(),
+            buffer: None,
+            defragmenting: false,
+        }
+    }
+
+    // (offset, count)
+    pub fn get(&self, handle: &Handle<Mesh>) -> Option<(usize, usize)> {
+        self.allocations
+            .get(handle)
+            .map(|range| (range.start, range.len()))
+    }
+
+    // replaces any existing data for the handle
+    pub fn insert(&mut self, handle: &Handle<Mesh>, items: &[T]) {
+        self.remove(handle);
+
+        if items.is_empty() {
+            return;
+        }
+
+        let start = self.alloc(items.len());
+        let range = start..start + items.len();
+        self.data[range.clone()].copy_from_slice(items);
+        self.dirty.push(range.clone());
+        self.by_start.insert(start, handle.clone_weak());
+        self.allocations.insert(handle.clone_weak(), range);
+    }
+
+    pub fn remove(&mut self, handle: &Handle<Mesh>) {
+        if let Some(range) = self.allocations.remove(handle) {
+            self.by_start.remove(&range.start);
+            self.release(range);
+        }
+    }
+
+    fn free_count(&self) -> usize {
+        self.free.iter().map(|range| range.len()).sum()
+    }
+
+    // first fit from the free list, else grow
+    fn alloc(&mut self, count: usize) -> usize {
+        if let Some(ix) = self.free.iter().position(|range| range.len() >= count) {
+            let start = self.free[ix].start;
+            self.free[ix].start += count;
+            if self.free[ix].is_empty() {
+                self.free.remove(ix);
+            }
+            return start;
+        }
+
+        let start = self.data.len();
+        self.data.resize(start + count, Zeroable::zeroed());
+        start
+    }
+
+    fn release(&mut self, range: Range<usize>) {
+        let ix = self.free.partition_point(|free| free.start < range.start);
+        self.free.insert(ix, range);
+
+        // coalesce with neighbours
+        if ix + 1 < self.free.len() && self.free[ix].end == self.free[ix + 1].start {
+            self.free[ix].end = self.free.remove(ix + 1).end;
+        }
+        if ix > 0 && self.free[ix - 1].end == self.free[ix].start {
+            self.free[ix - 1].end = self.free.remove(ix).end;
+        }
+
+        // drop a free tail
+        if self.free.last().map_or(false, |last| last.end == self.data.len()) {
+            let last = self.free.pop().unwrap();
+            self.data.truncate(last.start);
+        }
+    }
+
+    // close holes by moving the allocations after them down, a bounded amount each frame
+    pub fn defragment(&mut self) {
+        if !self.defragmenting {
+            if self.free_count() * DEFRAG_THRESHOLD <= self.data.len() {
+                return;
+            }
+            self.defragmenting = true;
+        }
+
+        let mut budget = DEFRAG_BUDGET;
+        while budget > 0 {
+            let Some(hole) = self.free.first().cloned() else {
+                self.defragmenting = false;
+                return;
+            };
+
+            // the tail is never free so there is always an allocation after a hole
+            let handle = self.by_start.remove(&hole.end).unwrap();
+            let range = self.allocations.get_mut(&handle).unwrap();
+            let count = range.len();
+
+            self.data.copy_within(range.clone(), hole.start);
+            *range = hole.start..hole.start + count;
+            self.dirty.push(range.clone());
+            self.by_start.insert(hole.start, handle);
+
+            self.free.remove(0);
+            self.release(hole.start + count..hole.end + count);
+
+            budget = budget.saturating_sub(count);
+        }
+    }
+
+    // write changed ranges, or everything if the gpu buffer needs to grow
+    pub fn write_buffer(&mut self, device: &RenderDevice, queue: &RenderQueue) {
+        let item_size = std::mem::size_of::<T>();
+        let capacity = self.buffer.as_ref().map_or(0, |(_, capacity)| *capacity);
+
+        if self.buffer.is_none() || capacity < self.data.len() {
+            let capacity = (self.data.len() + self.data.len() / 2).max(MIN_CAPACITY);
+            let buffer = device.create_buffer(&BufferDescriptor {
+                label: Some(self.label),
+                size: (capacity * item_size) as u64,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.buffer = Some((buffer, capacity));
+            self.dirty.clear();
+            self.dirty.push(0..self.data.len());
+        }
+
+        let ranges = self.take_dirty();
+        let buffer = &self.buffer.as_ref().unwrap().0;
+        for range in ranges {
+            queue.write_buffer(buffer, (range.start * item_size) as u64, cast_slice(&self.data[range]));
+        }
+    }
+
+    // sorted dirty ranges within the data, with overlapping / adjacent ranges merged so we issue as few writes as
+    // possible
+    fn take_dirty(&mut self) -> Vec<Range<usize>> {
+        self.dirty.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::new();
+        for range in self.dirty.drain(..) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        let len = self.data.len();
+        merged.retain_mut(|range| {
+            range.end = range.end.min(len);
+            !range.is_empty()
+        });
+        merged
+    }
+
+    pub fn binding(&self) -> Option<BindingResource> {
+        self.buffer.as_ref().map(|(buffer, _)| buffer.as_entire_binding())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::HandleId;
+
+    use super::*;
+
+    fn handle() -> Handle<Mesh> {
+        Handle::weak(HandleId::random::<Mesh>())
+    }
+
+    fn contents<'a>(allocator: &'a MeshAllocator<u32>, handle: &Handle<Mesh>) -> &'a [u32] {
+        let (offset, count) = allocator.get(handle).unwrap();
+        &allocator.data[offset..offset + count]
+    }
+
+    // allocations and free ranges tile the data exactly, and by_start mirrors the allocations
+    fn check_layout(allocator: &MeshAllocator<u32>) {
+        let mut ranges: Vec<_> = allocator.allocations.values().chain(allocator.free.iter()).cloned().collect();
+        ranges.sort_by_key(|range| range.start);
+        let mut end = 0;
+        for range in ranges {
+            assert_eq!(range.start, end);
+            assert!(!range.is_empty());
+            end = range.end;
+        }
+        assert_eq!(end, allocator.data.len());
+
+        assert_eq!(allocator.by_start.len(), allocator.allocations.len());
+        for (start, handle) in allocator.by_start.iter() {
+            assert_eq!(allocator.allocations[handle].start, *start);
+        }
+    }
+
+    #[test]
+    fn reuses_freed_ranges() {
+        let mut allocator = MeshAllocator::<u32>::new("test");
+        let (a, b, c, d) = (handle(), handle(), handle(), handle());
+        allocator.insert(&a, &[0; 8]);
+        allocator.insert(&b, &[1; 8]);
+        allocator.insert(&c, &[2; 8]);
+
+        allocator.remove(&a);
+        assert_eq!(allocator.get(&a), None);
+        assert_eq!(allocator.free, vec![0..8]);
+
+        // first fit into the hole
+        allocator.insert(&d, &[3; 4]);
+        assert_eq!(allocator.get(&d), Some((0, 4)));
+        assert_eq!(allocator.free, vec![4..8]);
+
+        // reinserting frees the old range first, which joins the hole before it
+        allocator.insert(&b, &[4; 2]);
+        assert_eq!(allocator.get(&b), Some((4, 2)));
+        assert_eq!(contents(&allocator, &b), &[4, 4]);
+        assert_eq!(allocator.free, vec![6..16]);
+        assert_eq!(contents(&allocator, &c), &[2; 8]);
+        check_layout(&allocator);
+
+        // too big for the hole, so it grows
+        allocator.insert(&a, &[5; 12]);
+        assert_eq!(allocator.get(&a), Some((24, 12)));
+        assert_eq!(allocator.data.len(), 36);
+        check_layout(&allocator);
+    }
+
+    #[test]
+    fn coalesces_adjacent_free_ranges() {
+        let mut allocator = MeshAllocator::<u32>::new("test");
+        let handles: Vec<_> = (0..4).map(|_| handle()).collect();
+        for (i, handle) in handles.iter().enumerate() {
+            allocator.insert(handle, &[i as u32; 4]);
+        }
+
+        allocator.remove(&handles[0]);
+        allocator.remove(&handles[2]);
+        assert_eq!(allocator.free, vec![0..4, 8..12]);
+
+        // joins both neighbours
+        allocator.remove(&handles[1]);
+        assert_eq!(allocator.free, vec![0..12]);
+        check_layout(&allocator);
+
+        // a free tail is dropped entirely
+        allocator.remove(&handles[3]);
+        assert!(allocator.free.is_empty());
+        assert!(allocator.data.is_empty());
+        check_layout(&allocator);
+    }
+
+    #[test]
+    fn merges_dirty_ranges() {
+        let mut allocator = MeshAllocator::<u32>::new("test");
+        let (a, b, c, d) = (handle(), handle(), handle(), handle());
+        allocator.insert(&a, &[0; 4]);
+        allocator.insert(&b, &[1; 4]);
+        assert_eq!(allocator.take_dirty(), vec![0..8]);
+        assert!(allocator.take_dirty().is_empty());
+
+        allocator.insert(&c, &[2; 4]);
+        allocator.remove(&a);
+        allocator.insert(&d, &[3; 2]);
+        assert_eq!(allocator.take_dirty(), vec![0..2, 8..12]);
+
+        // ranges past a dropped tail are clipped away
+        allocator.insert(&a, &[4; 4]);
+        allocator.remove(&a);
+        assert!(allocator.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn defragment_preserves_allocations() {
+        let mut allocator = MeshAllocator::<u32>::new("test");
+        let handles: Vec<_> = (0..8).map(|_| handle()).collect();
+        for (i, handle) in handles.iter().enumerate() {
+            let items: Vec<u32> = (0..(i as u32 + 1) * 3).map(|j| i as u32 * 1000 + j).collect();
+            allocator.insert(handle, &items);
+        }
+        let expected: Vec<Vec<u32>> = handles.iter().map(|handle| contents(&allocator, handle).to_vec()).collect();
+
+        for removed in [0, 2, 3, 5] {
+            allocator.remove(&handles[removed]);
+        }
+        assert!(allocator.free_count() * DEFRAG_THRESHOLD > allocator.data.len());
+        allocator.take_dirty();
+
+        allocator.defragment();
+        assert!(!allocator.defragmenting);
+        assert!(allocator.free.is_empty());
+        check_layout(&allocator);
+
+        let live = [1, 4, 6, 7];
+        let mut end = 0;
+        for i in live {
+            assert_eq!(contents(&allocator, &handles[i]), expected[i].as_slice());
+            end += expected[i].len();
+        }
+        assert_eq!(allocator.data.len(), end);
+        for i in [0, 2, 3, 5] {
+            assert_eq!(allocator.get(&handles[i]), None);
+        }
+
+        // everything that moved gets rewritten
+        assert_eq!(allocator.take_dirty(), vec![0..end]);
+    }
+}
@@ -9,7 +9,7 @@ use bevy::{
             FragmentState, MultisampleState, Operations, PipelineCache, PrimitiveState,
             RenderPassDescriptor, RenderPipelineDescriptor,
             ShaderStages, ShaderType,
-            BindGroupEntry, BindGroupDescriptor, BindingResource, BindGroup, SamplerDescriptor, AddressMode, FilterMode, SamplerBindingType,
+            BindGroupEntry, BindGroupDescriptor, BindGroup,
         },
         renderer::RenderDevice,
         view::{ViewTarget, ViewUniform, ViewUniforms, ViewUniformOffset},
@@ -17,7 +17,7 @@ use bevy::{
     },
 };
 
-use crate::render::{SdfCascadeInfos, SdfOutputData};
+use crate::render::sample::SdfSampleBindGroup;
 
 pub const DEBUG_NODE: &str = "sdf_debug_node";
 
@@ -82,43 +82,18 @@ impl FromWorld for DebugSdfPipeline {
                     },
                     count: None,
                 },
-                // cascade infos
-                BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Buffer { 
-                        ty: BufferBindingType::Storage { read_only: true }, 
-                        has_dynamic_offset: false, 
-                        min_binding_size: Some(SdfCascadeInfos::min_size()),
-                    },
-                    count: None,
-                },
-                // sdf texture
-                BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Texture {
-                        sample_type: bevy::render::render_resource::TextureSampleType::Float { filterable: true },
-                        view_dimension: bevy::render::render_resource::TextureViewDimension::D3,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                // sampler
-                BindGroupLayoutEntry {
-                    binding: 3,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                    count: None,
-                },
             ],
         });
 
+        // the sdf is read through sdf::sample at group 1
+        world.init_resource::<SdfSampleBindGroup>();
+        let sample_layout = world.resource::<SdfSampleBindGroup>().layout.clone();
+
         let debug_shader = world.resource::<AssetServer>().load("shader/debug.wgsl");
 
         let descriptor = RenderPipelineDescriptor {
             label: Some("debug sdf pipeline".into()),
-            layout: Some(vec![layout.clone()]),
+            layout: Some(vec![layout.clone(), sample_layout]),
             vertex: fullscreen_shader_vertex_state(),
             fragment: Some(FragmentState {
                 shader: debug_shader,
@@ -160,28 +135,12 @@ impl ExtractComponent for DebugSdf {
 
 fn queue_debug_view_bindgroup(
     render_device: Res<RenderDevice>,
-    output_data: Res<SdfOutputData>,
     view_uniforms: Res<ViewUniforms>,
     mut pipeline: ResMut<DebugSdfPipeline>,
 ) {
     pipeline.bind_group = None;
 
-    let header = match output_data.sdf_header_buffer.binding() {
-        Some(b) => b,
-        None => return,
-    };
-
     if let Some(view_binding) = view_uniforms.uniforms.binding() {
-        let sampler = render_device.create_sampler(&SamplerDescriptor{ 
-            address_mode_u: AddressMode::ClampToEdge,
-            address_mode_v: AddressMode::ClampToEdge,
-            address_mode_w: AddressMode::ClampToEdge,
-            mag_filter: FilterMode::Linear,
-            min_filter: FilterMode::Linear,
-            mipmap_filter: FilterMode::Linear,
-            ..Default::default()
-        });
-    
         let view_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
             label: Some("sdf debug view bindgroup"),
             entries: &[
@@ -189,18 +148,6 @@ fn queue_debug_view_bindgroup(
                     binding: 0,
                     resource: view_binding.clone(),
                 },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: header,
-                },
-                BindGroupEntry {
-                    binding: 2,
-                    resource: BindingResource::TextureView(&output_data.output_buffer.as_ref().unwrap().1),
-                },
-                BindGroupEntry {
-                    binding: 3,
-                    resource: BindingResource::Sampler(&sampler),
-                },
             ],
             layout: &pipeline.layout,
         });
@@ -257,6 +204,10 @@ impl Node for DebugSdfNode {
             Some(g) => g,
             None => return Ok(()),
         };
+        let sample_bind_group = match world.resource::<SdfSampleBindGroup>().bind_group.as_ref() {
+            Some(g) => g,
+            None => return Ok(()),
+        };
 
         let mut pass = render_context
             .command_encoder
@@ -268,6 +219,7 @@ impl Node for DebugSdfNode {
 
         pass.set_pipeline(pipeline);
         pass.set_bind_group(0, bind_group, &[uniform_offset.offset]);
+        pass.set_bind_group(1, sample_bind_group, &[]);
         pass.draw(0..3, 0..1);
 
         Ok(())
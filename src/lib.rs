@@ -1,89 +1,383 @@
 
-// max total triangles we can handle in one go (scene can be larger as long as it is not all within a single cascade update)
-// we preallocate xxx bytes per triangle of gpu storage buffer space for intermediate data
-// this should be removed - we can preprocess triangles on mesh load on cpu then coarse.wgsl doesn't need to process them 
-pub const MAX_TRI_COUNT: usize = 1 << 23;
-
-// tile count in each dimension
-// the constraint for this is workgroup shared memory - we make heavy use of LDS atomics in coarse.wgsl
-pub const TILE_DIM_COUNT: usize = 16;
-
-// number of voxels per tile dimension (so total voxels = tile_dim^3 * vox_per_tile_dim^3)
-// this is the resolution of the final SDF
-// we need voxels per dim (== voxels_per_tile_dim * tile_dim_count) to be <= 128 to fit our jump offsets into an rgba8sint texture
-// if you make this larger you'll need to update the nearest_jfa texture type as well
-pub const VOXELS_PER_TILE_DIM: usize = 8;
-
-// subvoxels per voxel
-// must be 1-4
-// if 1 or 2, the rg32uint jfa seed texture could be r8uint instead of rg32uint
-// in theory > 4 would work but texture requirements get larger and the code to write them in fine / fine_blend.wgsl would need updating
-// using multiple subvoxels gives a smoother final SDF and allows more granular occlusion
-pub const SUBVOXELS_PER_VOXEL_DIM: usize = 4;
-
-// total tiles / buckets
-pub const TILE_COUNT: usize = TILE_DIM_COUNT * TILE_DIM_COUNT * TILE_DIM_COUNT;
-
-// max tri/tile intersections. it will screw up visuals if we exceed this, and probably be slow as well. reduce scene complexity / sdf volume
-// we need 1 slot for each tile that each triangle touches
-// this is quite memory hungry, can def be reduced for lower complexity scenes
-pub const MAX_ID_COUNT: usize = 8192 * TILE_COUNT;
-
-// when bucketing in coarse.wgsl to create data to feed to the fine.wgsl stage, when we have more than MAX_IDS_PER_TILE we will create a new tile with the 
-// same location that gets blended into a final composite result in fine_blend.wgsl
-pub const MAX_IDS_PER_TILE: usize = 128 * 4;
-
-// max number of virtual tiles (tiles output by coarse.wgsl)
-pub const MAX_TILES: usize = TILE_COUNT * 16;
-
-
-
+mod allocator;
 mod debug;
+mod query;
 mod render;
 
 pub use debug::{DebugSdfPlugin, DebugSdf};
+pub use query::{SceneSdf, SdfHit};
+pub use render::cpu::{SdfCpuOutput, SdfCpuCascade};
+pub use render::gpu_query::{SdfQueries, SdfQuery, SdfQueryBatch, SdfQueryResponse, SdfQueryResult};
+pub use render::sample::{SdfSampleBindGroup, SetSdfSampleBindGroup};
 
-use bevy::prelude::*;
+use std::{fmt, time::Duration};
 
+use bevy::{prelude::*, render::{primitives::Aabb, RenderApp}};
+
+// uses the gpu backend when the app has a renderer (add it after `DefaultPlugins` / `RenderPlugin`), and the cpu
+// backend otherwise. insert an `SdfBackend` resource before adding the plugin to choose
 pub struct SceneSdfPlugin;
 
-// cascade will try to update when frame.0 % frequency == offset
-// if an earlier cascade is also scheduled for the same frame and needs to update then it will take priority
-// only 1 cascade update is ever run in a single frame
-// todo make it update the most needy cascade automatically
+// where the sdf is generated
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SdfBackend {
+    // compute shaders in the render app, output in the render world's `SdfOutputData` texture
+    Gpu,
+    // tasks on the `AsyncComputeTaskPool` against main world `Mesh` assets, output in `SdfCpuOutput`.
+    // for headless apps: works with `MinimalPlugins` plus `TransformPlugin`, an `AssetPlugin` and `add_asset::<Mesh>()`.
+    // unsigned only, and ignores `narrow_band`, `output_format` and `vertex_format` (with a warning for the first three)
+    Cpu,
+}
+
+// when a cascade is allowed to update
+// with `SchedulingMode::Fixed`, if an earlier cascade is also due on the same frame and needs to update then it will take priority
+// only 1 static cascade update is ever run in a single frame
+// cascades that have never been drawn (or have had a rebuild requested with `SdfRebuild`) are always due
 // todo should most of this be async compute ..? would probably need a double/triple buffer on output at least
 //  and some thought about cascade info settings used in render loop
 #[derive(Clone, Debug)]
-pub struct CascadeUpdateSchedule {
-    pub frequency: u32,
-    pub offset: u32,
+pub enum CascadeUpdateSchedule {
+    // when frame.0 % frequency == offset. frequency 0 is never due.
+    // ignored by `SchedulingMode::MostNeedy`, which picks its own frames
+    Frames { frequency: u32, offset: u32 },
+    // at most once per interval of wall-clock time, independent of frame rate
+    Interval(Duration),
+    // only when the centre has moved far enough to scroll the cascade. regions dirtied by casters changing wait for the
+    // next move
+    OnCentreMove,
+    // only when requested with `SdfRebuild`
+    Paused,
+}
+
+impl CascadeUpdateSchedule {
+    pub fn is_due(&self, frame: u32, since_last_update: Duration, centre_moved: bool) -> bool {
+        match self {
+            CascadeUpdateSchedule::Frames { frequency, offset } => frame.checked_rem(*frequency) == Some(*offset),
+            CascadeUpdateSchedule::Interval(interval) => since_last_update >= *interval,
+            CascadeUpdateSchedule::OnCentreMove => centre_moved,
+            CascadeUpdateSchedule::Paused => false,
+        }
+    }
+}
+
+// send to revoxelize parts of the sdf
+#[derive(Clone, Debug)]
+pub enum SdfRebuild {
+    // fully revoxelize all cascades on their next update, regardless of their schedules
+    All,
+    // fully revoxelize a single cascade (by index) on its next update, regardless of its schedule
+    Cascade(usize),
+    // revoxelize the tiles overlapping a world space region (e.g. procedurally generated geometry)
+    // in every cascade it touches, on their next updates regardless of their schedules
+    Region(Aabb),
 }
 
 #[derive(Clone, Debug)]
 pub struct CascadeSettings {
     pub far_distance: f32,
     pub update_schedule: CascadeUpdateSchedule,
+    // schedule for revoxelizing the dynamic layer (entities marked with `SdfDynamic`)
+    // unlike the static schedule, any number of cascades can update their dynamic layer in a single frame
+    // cascades with no dynamic casters in range are skipped
+    pub dynamic_update_schedule: CascadeUpdateSchedule,
+    // distances are clamped to +/- this many voxels in the output texture. the normalized `SdfOutputFormat`s spread
+    // their precision over this band, so keep it as small as the consumers allow. None leaves float formats
+    // unclamped, and gives the normalized formats a band as wide as the cascade
+    pub truncation: Option<u32>,
+}
+
+// how the single static cascade update each frame is chosen
+#[derive(Clone, Debug, Default)]
+pub enum SchedulingMode {
+    // each cascade updates on its `update_schedule`
+    #[default]
+    Fixed,
+    // each due cascade with work to do is scored every frame and the highest score is updated.
+    // `CascadeUpdateSchedule::Frames` schedules are treated as always due
+    MostNeedy(NeedWeights),
+}
+
+// score = (redraw * tiles scrolled + dirty * dirty tiles / tiles per slab)
+//       * (1 + staleness * frames since last update)
+//       / (1 + distance * world distance from the centre to the nearest region needing work)
+// cascades that have never been drawn always go first
+#[derive(Clone, Debug)]
+pub struct NeedWeights {
+    pub redraw: f32,
+    pub dirty: f32,
+    pub staleness: f32,
+    pub distance: f32,
+}
+
+impl Default for NeedWeights {
+    fn default() -> Self {
+        Self {
+            redraw: 1.0,
+            dirty: 1.0,
+            staleness: 0.1,
+            distance: 0.2,
+        }
+    }
 }
 
 impl CascadeSettings {
-    fn tile_size(&self) -> f32 {
-        self.far_distance / TILE_DIM_COUNT as f32 * 2.0
+    fn tile_size(&self, tile_dim_count: u32) -> f32 {
+        self.far_distance / tile_dim_count as f32 * 2.0
+    }
+}
+
+// resolution of every cascade. each cascade is tile_dim_count^3 tiles of voxels_per_tile_dim^3 voxels, and
+// voxelization runs at subvoxels_per_voxel_dim times the voxel resolution.
+// changing it at runtime reallocates all the gpu buffers and textures, recompiles the shaders and redraws every
+// cascade from scratch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SdfResolution {
+    // tile count in each dimension
+    // the coarse scan in coarse.wgsl runs in a single workgroup, handling tile_count / 1024 tiles per thread
+    pub tile_dim_count: u32,
+
+    // number of voxels per tile dimension, 1-8 (fine.wgsl keeps a tile's subvoxel bits in workgroup memory)
+    // voxels per dim (== voxels_per_tile_dim * tile_dim_count) is the resolution of the final SDF. it must be a
+    // multiple of 32 and at most 2048. jump offsets are stored as rgba8sint up to 128, rgba16sint above that.
+    // the cascades sit side by side in one texture, so voxels per dim * cascade count must also fit the device's
+    // max_texture_dimension_3d
+    pub voxels_per_tile_dim: u32,
+
+    // subvoxels per voxel, 1-5 (the jfa seed texture is rg32uint for up to 64 subvoxel bits, rgba32uint above that)
+    // using multiple subvoxels gives a smoother final SDF and allows more granular occlusion
+    pub subvoxels_per_voxel_dim: u32,
+
+    // max tri/tile intersections. it will screw up visuals if we exceed this, and probably be slow as well. reduce
+    // scene complexity / sdf volume
+    // we need 1 slot for each tile that each triangle touches
+    // this is quite memory hungry (4 bytes each), can def be reduced for lower complexity scenes
+    pub max_id_count: u32,
+
+    // when bucketing in coarse.wgsl to create data to feed to the fine.wgsl stage, when we have more than
+    // max_ids_per_tile we will create a new tile with the same location that gets blended into a final composite
+    // result in fine_blend.wgsl
+    pub max_ids_per_tile: u32,
+
+    // max number of virtual tiles (tiles output by coarse.wgsl), at least the tile count
+    pub max_tiles: u32,
+}
+
+// default max_id_count ceiling, 128MiB of ids to fit wgpu's default max_storage_buffer_binding_size
+const MAX_DEFAULT_ID_COUNT: u32 = 1 << 25;
+
+impl SdfResolution {
+    // limits are scaled with the tile count. triangles sit on surfaces, so the ids scale with the tile area rather
+    // than the volume, up to MAX_DEFAULT_ID_COUNT
+    pub fn new(tile_dim_count: u32, voxels_per_tile_dim: u32, subvoxels_per_voxel_dim: u32) -> Self {
+        Self {
+            tile_dim_count,
+            voxels_per_tile_dim,
+            subvoxels_per_voxel_dim,
+            max_id_count: tile_dim_count.saturating_pow(2).saturating_mul(1 << 17).min(MAX_DEFAULT_ID_COUNT),
+            max_ids_per_tile: 128 * 4,
+            max_tiles: tile_dim_count.saturating_pow(3).saturating_mul(16),
+        }
+    }
+
+    // total tiles / buckets. saturates, `validate` rejects resolutions where it doesn't fit
+    pub fn tile_count(&self) -> u32 {
+        self.tile_dim_count.saturating_pow(3)
+    }
+
+    pub fn voxels_per_dim(&self) -> u32 {
+        self.tile_dim_count * self.voxels_per_tile_dim
+    }
+
+    pub fn voxels_per_tile(&self) -> u32 {
+        self.voxels_per_tile_dim * self.voxels_per_tile_dim * self.voxels_per_tile_dim
+    }
+
+    pub fn validate(&self) -> Result<(), SdfResolutionError> {
+        if self.tile_dim_count == 0 {
+            return Err(SdfResolutionError::TileDimCount(self.tile_dim_count));
+        }
+        if !(1..=8).contains(&self.voxels_per_tile_dim) {
+            return Err(SdfResolutionError::VoxelsPerTileDim(self.voxels_per_tile_dim));
+        }
+        let voxels_per_dim = self.tile_dim_count.saturating_mul(self.voxels_per_tile_dim);
+        if voxels_per_dim > 2048 || voxels_per_dim % 32 != 0 {
+            return Err(SdfResolutionError::VoxelsPerDim(voxels_per_dim));
+        }
+        let tile_count = (self.tile_dim_count as u64).pow(3);
+        if tile_count > u32::MAX as u64 {
+            return Err(SdfResolutionError::TileCount(tile_count));
+        }
+        if !(1..=5).contains(&self.subvoxels_per_voxel_dim) {
+            return Err(SdfResolutionError::SubvoxelsPerVoxelDim(self.subvoxels_per_voxel_dim));
+        }
+        if self.max_ids_per_tile == 0 || self.max_id_count < self.max_ids_per_tile {
+            return Err(SdfResolutionError::MaxIdCount);
+        }
+        if self.max_tiles < self.tile_count() {
+            return Err(SdfResolutionError::MaxTiles);
+        }
+        Ok(())
+    }
+}
+
+impl Default for SdfResolution {
+    fn default() -> Self {
+        Self::new(16, 8, 4)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SdfResolutionError {
+    TileDimCount(u32),
+    VoxelsPerTileDim(u32),
+    VoxelsPerDim(u32),
+    TileCount(u64),
+    SubvoxelsPerVoxelDim(u32),
+    MaxIdCount,
+    MaxTiles,
+    // the resolution is valid but too big for the render device
+    BufferSize { buffer: &'static str, size: u64, limit: u64 },
+    TextureWidth { width: u64, limit: u32 },
+}
+
+impl fmt::Display for SdfResolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SdfResolutionError::TileDimCount(n) => write!(f, "tile_dim_count must be at least 1, got {n}"),
+            SdfResolutionError::VoxelsPerTileDim(n) => write!(f, "voxels_per_tile_dim must be 1-8, got {n}"),
+            SdfResolutionError::VoxelsPerDim(n) => write!(f, "tile_dim_count * voxels_per_tile_dim must be a multiple of 32 and at most 2048, got {n}"),
+            SdfResolutionError::TileCount(n) => write!(f, "tile_dim_count^3 must fit in a u32, got {n}"),
+            SdfResolutionError::SubvoxelsPerVoxelDim(n) => write!(f, "subvoxels_per_voxel_dim must be 1-5, got {n}"),
+            SdfResolutionError::MaxIdCount => write!(f, "max_ids_per_tile must be at least 1 and no more than max_id_count"),
+            SdfResolutionError::MaxTiles => write!(f, "max_tiles must be at least the tile count"),
+            SdfResolutionError::BufferSize { buffer, size, limit } => write!(f, "{buffer} buffer needs {size} bytes, the device allows {limit}"),
+            SdfResolutionError::TextureWidth { width, limit } => write!(f, "voxels per dim * cascade count is {width}, the device's max_texture_dimension_3d is {limit}"),
+        }
     }
 }
 
+impl std::error::Error for SdfResolutionError {}
+
+// which mesh entities are voxelized into the sdf
+// Marked: only entities with an `SdfCaster` component
+// Unmarked: all entities except those with a `NotSdfCaster` component
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ExtractionFilter {
     Marked,
     Unmarked,
 }
 
+impl ExtractionFilter {
+    pub fn includes(&self, marked: bool, opted_out: bool) -> bool {
+        match self {
+            ExtractionFilter::Marked => marked && !opted_out,
+            ExtractionFilter::Unmarked => !opted_out,
+        }
+    }
+}
+
+// add to an entity to include it in the sdf when using `ExtractionFilter::Marked`
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct SdfCaster;
+
+// add to an entity to exclude it from the sdf regardless of the filter (particles, first person weapons, skyboxes, etc)
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct NotSdfCaster;
+
+// add to an entity that moves (doors, vehicles, etc)
+// dynamic casters are revoxelized from scratch into a separate layer on the cascade's `dynamic_update_schedule`,
+// and merged with the static layer in the output pass. they never cause the static layer to be rebuilt
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct SdfDynamic;
+
+// minimum thickness (world units) of the entity's surfaces. each triangle is voxelized as a slab this thick, so
+// zero-thickness geometry (planes, cloth, foliage cards) always produces seeds whatever the `SdfVoxelization` mode.
+// distances are measured to the slab surface
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct SdfMinThickness(pub f32);
+
+// which subvoxels a triangle marks as seeds. with h = half a subvoxel:
+// - Conservative: every subvoxel the triangle touches at all (box overlap). thickest, never drops thin geometry
+// - TwentySixSeparating: subvoxels whose box the triangle's plane crosses, restricted in each axis-aligned projection
+//   to those whose centre is within h (l1 distance) of the projected triangle. no 26-connected path of subvoxels
+//   crosses the surface without hitting a seed
+// - SixSeparating: as above but the plane must also pass within h (l1 distance) of the centre, giving a surface one
+//   subvoxel thick along its dominant axis. no 6-connected path crosses it, but geometry thinner than a subvoxel
+//   can fall between the centres
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SdfVoxelization {
+    #[default]
+    Conservative,
+    SixSeparating,
+    TwentySixSeparating,
+}
+
+// format of the final sdf texture (SdfOutputData::output_buffer). the normalized formats store distances relative
+// to each cascade's `truncation` band, and sdf::sample (assets/shader/sample.wgsl) decodes them back to world
+// units with sdf::encoding. formats other than R32Float must support read-write storage on the adapter (native only),
+// otherwise we warn and fall back to R32Float
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SdfOutputFormat {
+    // 4 bytes per voxel, exact
+    #[default]
+    R32Float,
+    // 2 bytes per voxel, 11 bits of relative precision
+    R16Float,
+    // 1 byte per voxel, [-band, band] stored as [0, 1]
+    R8Unorm,
+    // 1 byte per voxel, [-band, band] stored as [-1, 1]
+    R8Snorm,
+}
+
+// gpu storage format for mesh vertex positions, applied to meshes as they are loaded or modified
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SdfVertexFormat {
+    // 12 bytes per vertex
+    #[default]
+    Float32,
+    // 6 bytes per vertex (padded to 8), quantized to 16 bits per axis relative to the mesh aabb.
+    // precision is the mesh extent / 65535, fine for anything smaller than a voxel
+    Unorm16,
+}
+
+// how the sign of the output distances is decided
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SdfSign {
+    // positive everywhere, including inside closed meshes
+    #[default]
+    Unsigned,
+    // negative inside closed meshes, found by counting surface crossings along each column of voxels.
+    // columns that cross an odd number of times (through holes in non-watertight meshes) take their sign from the
+    // nearest watertight column within `leak_tolerance` voxels, or are treated as outside if there is none.
+    // in-place rebuilds are extended to whole columns through the cascade
+    Signed { leak_tolerance: u32 },
+}
+
 // move this to scroll the cascade origins
 #[derive(Resource, Default)]
 pub struct SdfCentre(pub Vec3);
 
 #[derive(Resource)]
 pub struct SceneSdfSettings {
-    // not implemented
     pub filter: ExtractionFilter,
+    pub scheduling: SchedulingMode,
+    pub vertex_format: SdfVertexFormat,
+    pub voxelization: SdfVoxelization,
+    pub output_format: SdfOutputFormat,
+    // after each update, brute force check the distances at one voxel per tile against the nearby seeds and print
+    // the worst overestimate. very slow, for debugging. toggling it keeps the cascades, like `readback`, the filter
+    // and the schedules. other changes redraw every cascade
+    pub verify: bool,
+    // replace the jfa estimate with exact distances to the nearest triangle for voxels within this many voxels of a
+    // static surface. keep it small, each voxel in the band tests every triangle in the tiles around it
+    pub narrow_band: Option<u32>,
+    // keep `SceneSdf` up to date by copying each cascade back from the gpu after it updates (the cpu backend always
+    // does). turn off to save the copies and the readback buffers, about the size of the output texture
+    pub readback: bool,
+    pub sign: SdfSign,
+    // checked with `SdfResolution::validate` whenever the settings change, and against the render device's limits with
+    // the gpu backend. invalid resolutions are rejected with a warning, keeping the previous settings
+    pub resolution: SdfResolution,
     pub cascades: Vec<CascadeSettings>,
 }
 
@@ -91,55 +385,99 @@ impl Default for SceneSdfSettings {
     fn default() -> Self {
         Self {
             filter: ExtractionFilter::Unmarked,
+            scheduling: SchedulingMode::default(),
+            vertex_format: SdfVertexFormat::default(),
+            voxelization: SdfVoxelization::default(),
+            output_format: SdfOutputFormat::default(),
+            verify: false,
+            narrow_band: None,
+            readback: true,
+            sign: SdfSign::default(),
+            resolution: SdfResolution::default(),
             cascades: vec![
                 CascadeSettings {
                     far_distance: 3.0,
-                    update_schedule: CascadeUpdateSchedule {
+                    update_schedule: CascadeUpdateSchedule::Frames {
                         frequency: 3,
                         offset: 0,
                     },
+                    dynamic_update_schedule: CascadeUpdateSchedule::Frames {
+                        frequency: 1,
+                        offset: 0,
+                    },
+                    truncation: None,
                 },
                 CascadeSettings {
                     far_distance: 5.0,
-                    update_schedule: CascadeUpdateSchedule {
+                    update_schedule: CascadeUpdateSchedule::Frames {
                         frequency: 3,
                         offset: 1,
                     },
+                    dynamic_update_schedule: CascadeUpdateSchedule::Frames {
+                        frequency: 1,
+                        offset: 0,
+                    },
+                    truncation: None,
                 },
                 CascadeSettings {
                     far_distance: 7.5,
-                    update_schedule: CascadeUpdateSchedule {
+                    update_schedule: CascadeUpdateSchedule::Frames {
                         frequency: 15,
                         offset: 2,
                     },
+                    dynamic_update_schedule: CascadeUpdateSchedule::Frames {
+                        frequency: 4,
+                        offset: 0,
+                    },
+                    truncation: None,
                 },
                 CascadeSettings {
                     far_distance: 11.25,
-                    update_schedule: CascadeUpdateSchedule {
+                    update_schedule: CascadeUpdateSchedule::Frames {
                         frequency: 15,
                         offset: 5,
                     },
+                    dynamic_update_schedule: CascadeUpdateSchedule::Frames {
+                        frequency: 4,
+                        offset: 1,
+                    },
+                    truncation: None,
                 },
                 CascadeSettings {
                     far_distance: 17.0,
-                    update_schedule: CascadeUpdateSchedule {
+                    update_schedule: CascadeUpdateSchedule::Frames {
                         frequency: 15,
                         offset: 8,
                     },
+                    dynamic_update_schedule: CascadeUpdateSchedule::Frames {
+                        frequency: 4,
+                        offset: 2,
+                    },
+                    truncation: None,
                 },
                 CascadeSettings {
                     far_distance: 30.0,
-                    update_schedule: CascadeUpdateSchedule {
+                    update_schedule: CascadeUpdateSchedule::Frames {
                         frequency: 15,
                         offset: 11,
                     },
+                    dynamic_update_schedule: CascadeUpdateSchedule::Frames {
+                        frequency: 4,
+                        offset: 3,
+                    },
+                    truncation: None,
                 },
                 CascadeSettings {
                     far_distance: 60.0,
-                    update_schedule: CascadeUpdateSchedule {
+                    update_schedule: CascadeUpdateSchedule::Frames {
                         frequency: 15,
                         offset: 14,
                     },
+                    dynamic_update_schedule: CascadeUpdateSchedule::Frames {
+                        frequency: 4,
+                        offset: 0,
+                    },
+                    truncation: None,
                 },
             ],
         }
@@ -148,11 +486,23 @@ impl Default for SceneSdfSettings {
 
 impl Plugin for SceneSdfPlugin {
     fn build(&self, app: &mut App) {
+        let backend = match app.world.get_resource::<SdfBackend>() {
+            Some(backend) => *backend,
+            None if app.get_sub_app(RenderApp).is_ok() => SdfBackend::Gpu,
+            None => SdfBackend::Cpu,
+        };
+
         app
+        .insert_resource(backend)
         .init_resource::<SceneSdfSettings>()
         .init_resource::<SdfCentre>()
+        .add_event::<SdfRebuild>()
         ;
 
-        render::setup_render(app);
+        render::setup_scheduling(app);
+        match backend {
+            SdfBackend::Gpu => render::setup_render(app),
+            SdfBackend::Cpu => render::cpu::setup_cpu(app),
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,247 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+// main world copy of the sdf for gameplay queries. the gpu backend reads each cascade back from the output texture
+// a couple of frames after it updates (see SceneSdfSettings::readback), the cpu backend fills it as its tasks
+// finish, so results lag the scene slightly.
+// queries sample like sdf::sample (assets/shader/sample.wgsl), so they agree with SdfQueries and shaders: trilinear
+// filtering between voxel centres in the finest cascade that covers the point, blended into the next coarser one
+// near its border. cascades that haven't been read back yet are skipped.
+// settings changes keep the old cascades here until they are redrawn, unless they change the resolution, output
+// format or the cascades' count, far distances or truncation
+#[derive(Resource, Default, Clone)]
+pub struct SceneSdf {
+    // SdfState::layout_generation the cascades belong to
+    pub(crate) generation: u32,
+    voxels_per_dim: u32,
+    cascades: Vec<Option<MirroredCascade>>,
+}
+
+// distances below this count as touching a surface when raycasting, SdfQueries' default hit_threshold
+const RAYCAST_HIT_THRESHOLD: f32 = 0.01;
+const RAYCAST_MAX_STEPS: u32 = 250;
+// distances blend into the next coarser cascade over this many voxels from a cascade's border, BLEND_VOXELS in
+// sdf::sample
+const BLEND_VOXELS: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct SdfHit {
+    pub point: Vec3,
+    // normalized gradient at the hit, or facing back along the ray where it vanishes
+    pub normal: Vec3,
+    // along the ray from its origin
+    pub distance: f32,
+    // samples taken
+    pub steps: u32,
+}
+
+// one cascade of the output volume, decoded to world units
+#[derive(Clone)]
+pub(crate) struct MirroredCascade {
+    // world position of the cascade's minimum corner
+    pub(crate) min: Vec3,
+    pub(crate) voxel_size: f32,
+    // truncation band in world units, 0 for unclamped
+    pub(crate) band: f32,
+    // local voxels, x fastest
+    pub(crate) distances: Arc<[f32]>,
+}
+
+impl MirroredCascade {
+    // position in voxels from the first voxel centre, if the point is within the sampled range
+    // (half a voxel in from the cascade bounds, as in_cascade in sdf::sample)
+    fn texel(&self, point: Vec3, voxels_per_dim: u32) -> Option<Vec3> {
+        let texel = (point - self.min) / self.voxel_size - 0.5;
+        let max = Vec3::splat((voxels_per_dim - 1) as f32);
+        (texel.cmpge(Vec3::ZERO).all() && texel.cmple(max).all()).then_some(texel)
+    }
+
+    fn load(&self, voxel: UVec3, voxels_per_dim: u32) -> f32 {
+        self.distances[((voxel.z * voxels_per_dim + voxel.y) * voxels_per_dim + voxel.x) as usize]
+    }
+
+    // trilinear, clamped to the sampled range
+    fn sample(&self, texel: Vec3, voxels_per_dim: u32) -> f32 {
+        let max = (voxels_per_dim - 1) as f32;
+        let texel = texel.clamp(Vec3::ZERO, Vec3::splat(max));
+        let base = texel.floor().min(Vec3::splat((max - 1.0).max(0.0)));
+        let t = texel - base;
+        let base = base.as_uvec3();
+
+        let mut result = 0.0;
+        for corner in 0..8u32 {
+            let offset = UVec3::new(corner & 1, (corner >> 1) & 1, corner >> 2);
+            let weight = Vec3::select(offset.cmpeq(UVec3::ZERO), 1.0 - t, t);
+            let voxel = (base + offset).min(UVec3::splat(voxels_per_dim - 1));
+            result += weight.x * weight.y * weight.z * self.load(voxel, voxels_per_dim);
+        }
+        result
+    }
+
+    // central differences over a voxel
+    fn gradient(&self, texel: Vec3, voxels_per_dim: u32) -> Vec3 {
+        let sample = |offset: Vec3| self.sample(texel + offset, voxels_per_dim);
+        Vec3::new(
+            sample(Vec3::X) - sample(-Vec3::X),
+            sample(Vec3::Y) - sample(-Vec3::Y),
+            sample(Vec3::Z) - sample(-Vec3::Z),
+        ) / (2.0 * self.voxel_size)
+    }
+}
+
+impl SceneSdf {
+    pub(crate) fn reset(&mut self, generation: u32, voxels_per_dim: u32, cascade_count: usize) {
+        *self = Self {
+            generation,
+            voxels_per_dim,
+            cascades: vec![None; cascade_count],
+        };
+    }
+
+    pub(crate) fn set_cascade(&mut self, index: usize, cascade: MirroredCascade) {
+        if let Some(slot) = self.cascades.get_mut(index) {
+            *slot = Some(cascade);
+        }
+    }
+
+    // true once every cascade has been read back at least once
+    pub fn is_ready(&self) -> bool {
+        !self.cascades.is_empty() && self.cascades.iter().all(Option::is_some)
+    }
+
+    // finest read back cascade covering the point, with its index and the point's texel
+    fn covering(&self, point: Vec3) -> Option<(usize, &MirroredCascade, Vec3)> {
+        self.cascades.iter().enumerate().find_map(|(index, cascade)| {
+            let cascade = cascade.as_ref()?;
+            cascade.texel(point, self.voxels_per_dim).map(|texel| (index, cascade, texel))
+        })
+    }
+
+    // sample_distance in sdf::sample: the covering cascade, blended into the next coarser one within BLEND_VOXELS
+    // of its border so there is no seam. also returns the covering cascade and texel
+    fn sample_distance(&self, point: Vec3) -> Option<(f32, &MirroredCascade, Vec3)> {
+        let (index, cascade, texel) = self.covering(point)?;
+        let mut distance = cascade.sample(texel, self.voxels_per_dim);
+
+        let coarser = self.cascades
+            .get(index + 1)
+            .and_then(Option::as_ref)
+            .and_then(|coarser| coarser.texel(point, self.voxels_per_dim).map(|texel| (coarser, texel)));
+        if let Some((coarser, coarser_texel)) = coarser {
+            let edge = texel.min(Vec3::splat((self.voxels_per_dim - 1) as f32) - texel).min_element();
+            let weight = (edge / BLEND_VOXELS).clamp(0.0, 1.0);
+            if weight < 1.0 {
+                let coarse_distance = coarser.sample(coarser_texel, self.voxels_per_dim);
+                distance = coarse_distance + (distance - coarse_distance) * weight;
+            }
+        }
+
+        Some((distance, cascade, texel))
+    }
+
+    // distance to the nearest surface in world units, a lower bound accurate to about half a voxel of the cascade
+    // used. clamped to the cascade's truncation band when it has one. None outside every cascade
+    pub fn distance(&self, point: Vec3) -> Option<f32> {
+        self.sample_distance(point).map(|(distance, ..)| distance)
+    }
+
+    // central differences over a voxel of the covering cascade, roughly unit length away from the surface.
+    // pointing away from the nearest surface (outwards for signed distances)
+    pub fn gradient(&self, point: Vec3) -> Option<Vec3> {
+        let (_, cascade, texel) = self.covering(point)?;
+        Some(cascade.gradient(texel, self.voxels_per_dim))
+    }
+
+    // estimate of the nearest surface point, stepping the distance down the gradient. None outside every cascade,
+    // where the gradient vanishes (exactly between surfaces, or on an unsigned surface), or where the distance is
+    // truncated
+    pub fn closest_surface(&self, point: Vec3) -> Option<Vec3> {
+        let (distance, cascade, texel) = self.sample_distance(point)?;
+        if cascade.band > 0.0 && distance.abs() >= cascade.band {
+            return None;
+        }
+
+        let direction = cascade.gradient(texel, self.voxels_per_dim).try_normalize()?;
+        Some(point - direction * distance)
+    }
+
+    // sphere trace against the sdf, following sphere_trace in sdf::sample: the cascade is re-picked at every step,
+    // and steps are at least half a voxel of it so grazing rays keep moving. None if the ray misses within
+    // max_distance, leaves the sdf, or runs out of steps
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<SdfHit> {
+        let direction = direction.try_normalize()?;
+
+        let mut distance = 0.0;
+        for steps in 1..=RAYCAST_MAX_STEPS {
+            let point = origin + direction * distance;
+            let (dist, cascade, texel) = self.sample_distance(point)?;
+            if dist < RAYCAST_HIT_THRESHOLD {
+                let normal = cascade.gradient(texel, self.voxels_per_dim).try_normalize().unwrap_or(-direction);
+                return Some(SdfHit { point, normal, distance, steps });
+            }
+
+            let step = dist.max(0.5 * cascade.voxel_size);
+            if distance + step > max_distance {
+                return None;
+            }
+            distance += step;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a cascade of constant distance
+    fn constant(min: Vec3, voxel_size: f32, distance: f32) -> MirroredCascade {
+        MirroredCascade {
+            min,
+            voxel_size,
+            band: 0.0,
+            distances: vec![distance; 8 * 8 * 8].into(),
+        }
+    }
+
+    #[test]
+    fn distances_blend_into_the_coarser_cascade_at_the_border() {
+        let mut sdf = SceneSdf::default();
+        sdf.reset(0, 8, 2);
+        sdf.set_cascade(0, constant(Vec3::ZERO, 1.0, 1.0));
+        sdf.set_cascade(1, constant(Vec3::splat(-8.0), 2.0, 3.0));
+
+        // the fine cascade's first voxel centre is at 0.5, BLEND_VOXELS in from it is 2.5
+        assert_eq!(sdf.distance(Vec3::splat(4.0)), Some(1.0));
+        assert_eq!(sdf.distance(Vec3::new(2.5, 4.0, 4.0)), Some(1.0));
+        assert_eq!(sdf.distance(Vec3::new(1.5, 4.0, 4.0)), Some(2.0));
+        assert_eq!(sdf.distance(Vec3::new(0.5, 4.0, 4.0)), Some(3.0));
+        // only the coarse cascade covers it
+        assert_eq!(sdf.distance(Vec3::new(-1.0, 4.0, 4.0)), Some(3.0));
+        assert_eq!(sdf.distance(Vec3::splat(100.0)), None);
+
+        // without the coarser cascade there is nothing to blend into
+        sdf.reset(0, 8, 2);
+        sdf.set_cascade(0, constant(Vec3::ZERO, 1.0, 1.0));
+        assert_eq!(sdf.distance(Vec3::new(0.5, 4.0, 4.0)), Some(1.0));
+    }
+
+    #[test]
+    fn raycast_moves_between_cascades() {
+        let mut sdf = SceneSdf::default();
+        sdf.reset(0, 8, 2);
+        sdf.set_cascade(0, constant(Vec3::ZERO, 1.0, 0.25));
+        sdf.set_cascade(1, constant(Vec3::splat(-8.0), 2.0, 0.25));
+
+        // starts in the coarse cascade, steps through the fine one and leaves the sdf
+        assert!(sdf.raycast(Vec3::new(-6.0, 4.0, 4.0), Vec3::X, 100.0).is_none());
+
+        // a surface everywhere in the fine cascade is hit once it's no longer blended with the coarse one,
+        // BLEND_VOXELS in from the fine cascade's first voxel centre
+        sdf.set_cascade(0, constant(Vec3::ZERO, 1.0, 0.0));
+        let hit = sdf.raycast(Vec3::new(-6.0, 4.0, 4.0), Vec3::X, 100.0).unwrap();
+        assert_eq!(hit.point, Vec3::new(2.5, 4.0, 4.0));
+    }
+}
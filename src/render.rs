@@ -1,6 +1,6 @@
-use wgpu::{util::DispatchIndirect, BufferDescriptor, BufferUsages, QuerySet, ComputePass};
+use wgpu::{util::DispatchIndirect, BufferDescriptor, BufferUsages, QuerySet, ComputePass, TextureFormatFeatureFlags};
 
-use std::{borrow::Cow, num::NonZeroU64, sync::mpsc::{Receiver, Sender}};
+use std::{borrow::Cow, num::{NonZeroU32, NonZeroU64}, sync::{mpsc::{Receiver, Sender}, atomic::{AtomicBool, Ordering}, Arc, Mutex}, time::Duration};
 
 use bevy::{
     core::FrameCount,
@@ -9,6 +9,7 @@ use bevy::{
         extract_resource::{ExtractResource, ExtractResourcePlugin},
         mesh::VertexAttributeValues,
         primitives::{Aabb, Frustum, Plane},
+        view::VisibilitySystems,
         render_asset::ExtractedAssets,
         render_graph::{Node, RenderGraph},
         render_resource::{
@@ -19,103 +20,466 @@ use bevy::{
             ShaderType, StorageBuffer, StorageTextureAccess, Texture, TextureDescriptor,
             TextureFormat, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, Buffer,
         },
-        renderer::{RenderDevice, RenderQueue},
+        renderer::{RenderAdapter, RenderDevice, RenderQueue},
         Extract, RenderApp, RenderStage,
     },
-    utils::{HashMap, HashSet}, reflect::TypeUuid, math::Vec3A,
+    utils::{HashMap, HashSet}, reflect::TypeUuid, math::{Vec3A, Mat3A},
+    transform::TransformSystem,
 };
 
-use crate::*;
+use crate::{*, allocator::MeshAllocator, query::MirroredCascade};
+
+pub(crate) mod cpu;
+pub(crate) mod gpu_query;
+pub(crate) mod sample;
+
+// counts, write indices and offsets per tile, see CoarseBins in coarse.wgsl
+fn coarse_bins_size(resolution: &SdfResolution) -> u64 {
+    (resolution.tile_count() as usize * 3 * std::mem::size_of::<u32>()) as u64
+}
+
+// a bit per voxel, then a bit per column, see parity.wgsl
+fn sign_word_count(resolution: &SdfResolution) -> u32 {
+    let voxels_per_dim = resolution.voxels_per_dim() as u64;
+    ((voxels_per_dim * voxels_per_dim * voxels_per_dim + voxels_per_dim * voxels_per_dim) / 32) as u32
+}
+
+// u32s of subvoxel bits per voxel, 64 bits in an rg32uint seed texture, or 128 (up to 5^3 subvoxels) in rgba32uint
+fn seed_words(resolution: &SdfResolution) -> u32 {
+    match resolution.subvoxels_per_voxel_dim.pow(3) {
+        0..=64 => 2,
+        _ => 4,
+    }
+}
+
+// the resolution sized buffers and the side by side cascade textures against the device's limits, so a resolution
+// the device can't allocate is rejected like an invalid one. call after SdfResolution::validate
+fn check_device_limits(resolution: &SdfResolution, cascade_count: usize, limits: &wgpu::Limits) -> Result<(), SdfResolutionError> {
+    let buffer_limit = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+    let buffers = [
+        ("coarse counts per tile", UVec2::min_size().get() * resolution.max_tiles as u64),
+        ("coarse tile ids", u32::min_size().get() * resolution.max_id_count as u64),
+        ("coarse bins", coarse_bins_size(resolution)),
+        ("fine output", 4 * seed_words(resolution) as u64 * resolution.voxels_per_tile() as u64 * resolution.max_tiles as u64),
+        ("sign bits", 4 * sign_word_count(resolution) as u64),
+    ];
+    for (buffer, size) in buffers {
+        if size > buffer_limit {
+            return Err(SdfResolutionError::BufferSize { buffer, size, limit: buffer_limit });
+        }
+    }
+
+    let width = resolution.voxels_per_dim() as u64 * cascade_count as u64;
+    if width > limits.max_texture_dimension_3d as u64 {
+        return Err(SdfResolutionError::TextureWidth { width, limit: limits.max_texture_dimension_3d });
+    }
+    Ok(())
+}
+
+// main world copy of the render device's limits, for checking new settings (gpu backend only)
+#[derive(Resource)]
+struct SdfDeviceLimits(wgpu::Limits);
+
+fn seed_format(resolution: &SdfResolution) -> TextureFormat {
+    match seed_words(resolution) {
+        2 => TextureFormat::Rg32Uint,
+        _ => TextureFormat::Rgba32Uint,
+    }
+}
+
+// jump offsets fit into an rgba8sint texture up to 128 voxels per dim, beyond that we use rgba16sint
+fn nearest_format(resolution: &SdfResolution) -> TextureFormat {
+    match resolution.voxels_per_dim() {
+        0..=128 => TextureFormat::Rgba8Sint,
+        _ => TextureFormat::Rgba16Sint,
+    }
+}
+
+fn output_texture_format(format: SdfOutputFormat) -> TextureFormat {
+    match format {
+        SdfOutputFormat::R32Float => TextureFormat::R32Float,
+        SdfOutputFormat::R16Float => TextureFormat::R16Float,
+        SdfOutputFormat::R8Unorm => TextureFormat::R8Unorm,
+        SdfOutputFormat::R8Snorm => TextureFormat::R8Snorm,
+    }
+}
+
+// the output passes read and write the output texture, fall back to R32Float when the adapter can't
+fn supported_output_format(format: SdfOutputFormat, adapter: Option<&RenderAdapter>) -> SdfOutputFormat {
+    let Some(adapter) = adapter else {
+        return format;
+    };
+
+    let features = adapter.get_texture_format_features(output_texture_format(format));
+    if format != SdfOutputFormat::R32Float && !features.flags.contains(TextureFormatFeatureFlags::STORAGE_READ_WRITE) {
+        warn!("sdf output format {format:?} doesn't support read-write storage on this adapter, using R32Float");
+        return SdfOutputFormat::R32Float;
+    }
+    format
+}
+
+// selects the texture declarations in bind.wgsl / output.wgsl / output_bind.wgsl to match the formats above
+fn shader_defs(resolution: &SdfResolution, output_format: SdfOutputFormat) -> Vec<String> {
+    let mut defs = Vec::new();
+    if seed_format(resolution) == TextureFormat::Rgba32Uint {
+        defs.push("SDF_SEED_RGBA32".to_string());
+    }
+    if nearest_format(resolution) == TextureFormat::Rgba16Sint {
+        defs.push("SDF_NEAREST_RGBA16".to_string());
+    }
+    match output_format {
+        SdfOutputFormat::R32Float => (),
+        SdfOutputFormat::R16Float => defs.push("SDF_OUTPUT_R16FLOAT".to_string()),
+        SdfOutputFormat::R8Unorm => defs.push("SDF_OUTPUT_R8UNORM".to_string()),
+        SdfOutputFormat::R8Snorm => defs.push("SDF_OUTPUT_R8SNORM".to_string()),
+    }
+    defs
+}
+
+// max error, failures, samples, padding, see VerifyResults in verify.wgsl
+const VERIFY_RESULTS_SIZE: u64 = 4 * std::mem::size_of::<u32>() as u64;
 
 pub const SDF_CONSTS_SHADER_HANDLE: HandleUntyped =
     HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 9556909940226147828);
 
-pub fn setup_render(app: &mut App) {
+// main world scheduling of cascade updates, shared by both backends
+pub fn setup_scheduling(app: &mut App) {
     app
     .init_resource::<SdfState>()
-    .add_plugin(ExtractResourcePlugin::<SdfState>::default())
+    .init_resource::<SceneSdf>()
     .add_system_to_stage(CoreStage::PostUpdate, init_state_for_settings)
-    .add_system_to_stage(CoreStage::PostUpdate, update_cascades.after(init_state_for_settings)) // should be after AABB update except the whole thing only works for static anyway
+    .add_system_to_stage(CoreStage::PostUpdate, update_cascades
+        .after(init_state_for_settings)
+        // casters that moved or changed bounds are detected this frame
+        .after(TransformSystem::TransformPropagate)
+        .after(VisibilitySystems::CalculateBounds)
+    )
     ;
 
-    assert!(VOXELS_PER_DIM <= 128, "jfa_nearest requires local offset fits into signed byte");
+    if let Err(e) = app.world.resource::<SceneSdfSettings>().resolution.validate() {
+        panic!("invalid sdf resolution: {e}");
+    }
+}
 
-    const VOXELS_PER_DIM: usize = TILE_DIM_COUNT * VOXELS_PER_TILE_DIM;
-    const VOXELS_PER_TILE: usize = VOXELS_PER_TILE_DIM * VOXELS_PER_TILE_DIM * VOXELS_PER_TILE_DIM;
-    const FINE_OUTPUT_SIZE: usize = VOXELS_PER_TILE * MAX_TILES * 2;
-    let consts_str = format!(
-        "
-        #define_import_path sdf::consts
+pub fn setup_render(app: &mut App) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    app
+    .add_plugin(ExtractResourcePlugin::<SdfState>::default())
+    .insert_resource(SdfReadbackReceiver(Mutex::new(receiver)))
+    .add_system_to_stage(CoreStage::PreUpdate, receive_sdf_readback)
+    ;
 
-        let TILE_DIM_COUNT: u32 = {TILE_DIM_COUNT}u;
-        let VOXELS_PER_TILE_DIM: u32 = {VOXELS_PER_TILE_DIM}u;
-        let VOXELS_PER_DIM: u32 = {VOXELS_PER_DIM}u;
-        let SUBVOXELS_PER_VOXEL_DIM: u32 = {SUBVOXELS_PER_VOXEL_DIM}u;
-
-        let TILE_COUNT: u32 = {TILE_COUNT}u;
-        let VOXELS_PER_TILE: u32 = {VOXELS_PER_TILE}u;
-        let MAX_ID_COUNT: u32 = {MAX_ID_COUNT}u;
-        let MAX_IDS_PER_TILE: u32 = {MAX_IDS_PER_TILE}u;
-        let MAX_TILES: u32 = {MAX_TILES}u;
-        let FINE_OUTPUT_SIZE: u32 = {FINE_OUTPUT_SIZE}u;
-        "
-    );
+    let limits = app.world.resource::<RenderDevice>().limits();
+    let settings = app.world.resource::<SceneSdfSettings>();
+    if let Err(e) = check_device_limits(&settings.resolution, settings.cascades.len(), &limits) {
+        panic!("sdf resolution too big for the render device: {e}");
+    }
+    let resolution = settings.resolution.clone();
+    let output_format = supported_output_format(settings.output_format, app.world.get_resource::<RenderAdapter>());
+    app.insert_resource(SdfDeviceLimits(limits));
 
     let mut shader_assets = app.world.resource_mut::<Assets<Shader>>();
-    shader_assets.set_untracked(SDF_CONSTS_SHADER_HANDLE, Shader::from_wgsl_with_path(consts_str, std::file!()));
+    shader_assets.set_untracked(SDF_CONSTS_SHADER_HANDLE, consts_shader(&resolution, output_format));
 
     let render_app = app.sub_app_mut(RenderApp);
     render_app
-        .init_resource::<SceneSdfPipeline>()
         .init_resource::<SdfWorkingData>()
         .init_resource::<SdfOutputData>()
+        .insert_resource(SdfReadbackData::new(sender))
         .init_non_send_resource::<TimeStampQueue>()
         .add_system_to_stage(RenderStage::Extract, extract_sdf_meshes)
+        .add_system_to_stage(RenderStage::Prepare, prepare_sdf_pipeline)
         .add_system_to_stage(RenderStage::Prepare, prepare_sdf_meshes)
-        .add_system_to_stage(RenderStage::Queue, queue_sdf_data);
+        .add_system_to_stage(RenderStage::Queue, queue_sdf_data)
+        .add_system_to_stage(RenderStage::Queue, queue_sdf_readback.after(queue_sdf_data));
 
     let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
     render_graph.add_node("scene_sdf_node", SceneSdfNode);
+    render_graph.add_node("scene_sdf_readback_node", SdfReadbackNode);
     render_graph
         .add_node_edge(
             "scene_sdf_node",
+            "scene_sdf_readback_node",
+        )
+        .unwrap();
+    render_graph
+        .add_node_edge(
+            "scene_sdf_readback_node",
             bevy::render::main_graph::node::CAMERA_DRIVER,
         )
         .unwrap();
+
+    sample::setup_sdf_sample(app);
+    gpu_query::setup_gpu_queries(app);
+}
+
+// the sdf::consts module, regenerated when the resolution or output format changes
+fn consts_shader(resolution: &SdfResolution, output_format: SdfOutputFormat) -> Shader {
+    let tile_dim_count = resolution.tile_dim_count;
+    let voxels_per_tile_dim = resolution.voxels_per_tile_dim;
+    let voxels_per_dim = resolution.voxels_per_dim();
+    let subvoxels_per_voxel_dim = resolution.subvoxels_per_voxel_dim;
+    let tile_count = resolution.tile_count();
+    let voxels_per_tile = resolution.voxels_per_tile();
+    let max_id_count = resolution.max_id_count;
+    let max_ids_per_tile = resolution.max_ids_per_tile;
+    let max_tiles = resolution.max_tiles;
+    let seed_words = seed_words(resolution);
+    let seed_words_per_tile = voxels_per_tile * seed_words;
+    let fine_output_size = voxels_per_tile * max_tiles * seed_words;
+    let nearest_offset_limit = match nearest_format(resolution) {
+        TextureFormat::Rgba8Sint => 128,
+        _ => 32768,
+    };
+    let sign_word_count = sign_word_count(resolution);
+    const VERTEX_FORMAT_FLOAT32: u32 = SdfVertexFormat::Float32 as u32;
+    const VERTEX_FORMAT_UNORM16: u32 = SdfVertexFormat::Unorm16 as u32;
+    const VOXELIZATION_CONSERVATIVE: u32 = SdfVoxelization::Conservative as u32;
+    const VOXELIZATION_SIX_SEPARATING: u32 = SdfVoxelization::SixSeparating as u32;
+    const VOXELIZATION_TWENTY_SIX_SEPARATING: u32 = SdfVoxelization::TwentySixSeparating as u32;
+    const OUTPUT_FORMAT_R32FLOAT: u32 = SdfOutputFormat::R32Float as u32;
+    const OUTPUT_FORMAT_R16FLOAT: u32 = SdfOutputFormat::R16Float as u32;
+    const OUTPUT_FORMAT_R8UNORM: u32 = SdfOutputFormat::R8Unorm as u32;
+    const OUTPUT_FORMAT_R8SNORM: u32 = SdfOutputFormat::R8Snorm as u32;
+    let output_format = output_format as u32;
+    let consts_str = format!(
+        "
+        #define_import_path sdf::consts
+
+        let TILE_DIM_COUNT: u32 = {tile_dim_count}u;
+        let VOXELS_PER_TILE_DIM: u32 = {voxels_per_tile_dim}u;
+        let VOXELS_PER_DIM: u32 = {voxels_per_dim}u;
+        let SUBVOXELS_PER_VOXEL_DIM: u32 = {subvoxels_per_voxel_dim}u;
+
+        let TILE_COUNT: u32 = {tile_count}u;
+        let VOXELS_PER_TILE: u32 = {voxels_per_tile}u;
+        let MAX_ID_COUNT: u32 = {max_id_count}u;
+        let MAX_IDS_PER_TILE: u32 = {max_ids_per_tile}u;
+        let MAX_TILES: u32 = {max_tiles}u;
+        let FINE_OUTPUT_SIZE: u32 = {fine_output_size}u;
+
+        let SEED_WORDS: u32 = {seed_words}u;
+        let SEED_WORDS_PER_TILE: u32 = {seed_words_per_tile}u;
+        let NEAREST_OFFSET_LIMIT: i32 = {nearest_offset_limit};
+
+        let VERTEX_FORMAT_FLOAT32: u32 = {VERTEX_FORMAT_FLOAT32}u;
+        let VERTEX_FORMAT_UNORM16: u32 = {VERTEX_FORMAT_UNORM16}u;
+
+        let VOXELIZATION_CONSERVATIVE: u32 = {VOXELIZATION_CONSERVATIVE}u;
+        let VOXELIZATION_SIX_SEPARATING: u32 = {VOXELIZATION_SIX_SEPARATING}u;
+        let VOXELIZATION_TWENTY_SIX_SEPARATING: u32 = {VOXELIZATION_TWENTY_SIX_SEPARATING}u;
+
+        let OUTPUT_FORMAT_R32FLOAT: u32 = {OUTPUT_FORMAT_R32FLOAT}u;
+        let OUTPUT_FORMAT_R16FLOAT: u32 = {OUTPUT_FORMAT_R16FLOAT}u;
+        let OUTPUT_FORMAT_R8UNORM: u32 = {OUTPUT_FORMAT_R8UNORM}u;
+        let OUTPUT_FORMAT_R8SNORM: u32 = {OUTPUT_FORMAT_R8SNORM}u;
+        let OUTPUT_FORMAT: u32 = {output_format}u;
+
+        let SIGN_WORD_COUNT: u32 = {sign_word_count}u;
+        "
+    );
+
+    Shader::from_wgsl_with_path(consts_str, std::file!())
 }
 
 #[derive(Clone, Debug)]
 struct CascadeState {
     settings: CascadeSettings,
+    // from SdfResolution
+    tile_dim_count: i32,
     last_origin: Option<IVec3>,
     redraw: IVec3,
+    // local tile region [min, max) that the static layer will revoxelize this frame.
+    // when redraw is zero this is an in-place rebuild of a dirty region
+    update_region: Option<(IVec3, IVec3)>,
+    // world tile region [min, max) that needs revoxelizing due to casters changing
+    dirty: Option<(IVec3, IVec3)>,
+    // an SdfRebuild::Region touched the dirty region, so it's due regardless of the schedule
+    dirty_requested: bool,
+    // frame and time of the last static update
+    last_update_frame: u32,
+    last_update_time: Duration,
+    last_dynamic_update_time: Duration,
+    // rebuild the dynamic layer this frame
+    dynamic_redraw: bool,
+    // the dynamic layer contained casters at the last dynamic redraw, so needs clearing even if it is empty now
+    dynamic_occupied: bool,
 }
 
 #[derive(Default, Resource, ExtractResource, Clone)]
 struct SdfState {
     cascades: Vec<CascadeState>,
     visible_entities: HashSet<Entity>,
+    dynamic_entities: HashSet<Entity>,
+    vertex_format: SdfVertexFormat,
+    voxelization: SdfVoxelization,
+    verify: bool,
+    narrow_band: u32,
+    sign: SdfSign,
+    resolution: SdfResolution,
+    // the format we actually use, see supported_output_format
+    output_format: SdfOutputFormat,
+    readback: bool,
+    // bumped whenever new settings rebuild the cascades, the cpu backend starts over when it changes
+    generation: u32,
+    // bumped when new settings change where and how the cascades are stored (see layout_changed). SceneSdf and the
+    // readback buffers start over when it changes, otherwise they keep the old data until the cascades are redrawn
+    layout_generation: u32,
+}
+
+impl SdfState {
+    // signed flag and leak tolerance for SdfCascadeInfo
+    fn sign_info(&self) -> (u32, u32) {
+        match self.sign {
+            SdfSign::Unsigned => (0, 0),
+            SdfSign::Signed { leak_tolerance } => (1, leak_tolerance),
+        }
+    }
+
+    // truncation band in voxels for SdfCascadeInfo, 0 for unclamped
+    fn truncation(&self, cascade: &CascadeState) -> u32 {
+        match (cascade.settings.truncation, self.output_format) {
+            (Some(truncation), _) => truncation.max(1),
+            (None, SdfOutputFormat::R32Float | SdfOutputFormat::R16Float) => 0,
+            (None, SdfOutputFormat::R8Unorm | SdfOutputFormat::R8Snorm) => self.resolution.voxels_per_dim(),
+        }
+    }
+
+    // see stitch_region
+    fn stitch_region(&self, cascade: &CascadeState) -> Option<(IVec3, IVec3)> {
+        stitch_region(&self.resolution, cascade.redraw, cascade.update_region?, self.truncation(cascade))
+    }
+
+    // whether new settings change what the cascades hold, so they have to start over. verify, readback and the
+    // schedules are applied as they are, and update_cascades redraws around the casters a new filter adds or drops
+    fn needs_rebuild(&self, settings: &SceneSdfSettings, output_format: SdfOutputFormat) -> bool {
+        self.resolution != settings.resolution
+            || self.output_format != output_format
+            || self.vertex_format != settings.vertex_format
+            || self.voxelization != settings.voxelization
+            || self.narrow_band != settings.narrow_band.unwrap_or(0)
+            || self.sign != settings.sign
+            || self.layout_changed(settings, output_format)
+    }
+
+    // the resolution, output format or cascade layout changed, so the old cascades can't be kept in SceneSdf
+    fn layout_changed(&self, settings: &SceneSdfSettings, output_format: SdfOutputFormat) -> bool {
+        self.resolution != settings.resolution
+            || self.output_format != output_format
+            || self.cascades.len() != settings.cascades.len()
+            || self.cascades.iter().zip(&settings.cascades).any(|(state, settings)| {
+                state.settings.far_distance != settings.far_distance || state.settings.truncation != settings.truncation
+            })
+    }
+}
+
+// local voxel region [min, max) the stitch passes visit after a scroll: the scrolled-in slab, which the slab's own
+// jfa leaves up to a couple of voxels out far from its seeds, and the retained voxels that a slab seed could bring
+// inside the output band. that's the truncation band beside the slab, or the whole cascade when it's unclamped.
+// None if there is nothing to stitch (no scroll, or a full redraw). mirrored by stitch_min in jfa stitch.wgsl
+fn stitch_region(resolution: &SdfResolution, redraw: IVec3, (update_min, update_max): (IVec3, IVec3), truncation: u32) -> Option<(IVec3, IVec3)> {
+    let tile_dim_count = resolution.tile_dim_count as i32;
+    if redraw == IVec3::ZERO || update_max - update_min == IVec3::splat(tile_dim_count) {
+        return None;
+    }
+
+    let voxels_per_tile_dim = resolution.voxels_per_tile_dim as i32;
+    let voxels_per_dim = IVec3::splat(resolution.voxels_per_dim() as i32);
+    // a slab seed further than this can't make the output any smaller than the band
+    let reach = match truncation {
+        0 => voxels_per_dim,
+        truncation => IVec3::splat(truncation as i32 + 1),
+    };
+
+    // scrolls are along a single axis, the slab covers the whole cascade on the others
+    let (slab_min, slab_max) = (update_min * voxels_per_tile_dim, update_max * voxels_per_tile_dim);
+    Some((
+        IVec3::select(slab_min.cmpgt(IVec3::ZERO), (slab_min - reach).max(IVec3::ZERO), IVec3::ZERO),
+        IVec3::select(slab_max.cmplt(voxels_per_dim), (slab_max + reach).min(voxels_per_dim), voxels_per_dim),
+    ))
 }
 
-fn init_state_for_settings(settings: Res<SceneSdfSettings>, mut state: ResMut<SdfState>) {
+fn init_state_for_settings(
+    settings: Res<SceneSdfSettings>,
+    mut state: ResMut<SdfState>,
+    // not present for the cpu backend
+    shaders: Option<ResMut<Assets<Shader>>>,
+    adapter: Option<Res<RenderAdapter>>,
+    limits: Option<Res<SdfDeviceLimits>>,
+) {
     if settings.is_changed() {
+        if let Err(e) = settings.resolution.validate() {
+            warn!("ignoring sdf settings with invalid resolution: {e}");
+            return;
+        }
+        if let Some(SdfDeviceLimits(limits)) = limits.as_deref() {
+            if let Err(e) = check_device_limits(&settings.resolution, settings.cascades.len(), limits) {
+                warn!("ignoring sdf settings too big for the render device: {e}");
+                return;
+            }
+        }
+
+        let output_format = supported_output_format(settings.output_format, adapter.as_deref());
+        state.verify = settings.verify;
+        state.readback = settings.readback;
+        if !state.needs_rebuild(&settings, output_format) {
+            // keep the cascades, and SceneSdf with them
+            for (cascade, settings) in state.cascades.iter_mut().zip(&settings.cascades) {
+                cascade.settings = settings.clone();
+            }
+            return;
+        }
+
+        if let Some(mut shaders) = shaders {
+            if settings.resolution != state.resolution || output_format != state.output_format {
+                // pipelines importing sdf::consts are recompiled, and the render world reallocates everything
+                shaders.set_untracked(SDF_CONSTS_SHADER_HANDLE, consts_shader(&settings.resolution, output_format));
+            }
+        }
+
+        let layout_generation = match state.layout_changed(&settings, output_format) {
+            true => state.layout_generation.wrapping_add(1),
+            false => state.layout_generation,
+        };
         *state = SdfState {
             cascades: settings
                 .cascades
                 .iter()
-                .map(|settings| CascadeState {
-                    settings: settings.clone(),
+                .map(|cascade| CascadeState {
+                    settings: cascade.clone(),
+                    tile_dim_count: settings.resolution.tile_dim_count as i32,
                     last_origin: None,
                     redraw: IVec3::ZERO,
+                    update_region: None,
+                    dirty: None,
+                    dirty_requested: false,
+                    last_update_frame: 0,
+                    last_update_time: Duration::ZERO,
+                    last_dynamic_update_time: Duration::ZERO,
+                    dynamic_redraw: false,
+                    // the dynamic textures may hold data from previous settings
+                    dynamic_occupied: true,
                 })
                 .collect(),
             visible_entities: HashSet::default(),
+            dynamic_entities: HashSet::default(),
+            vertex_format: settings.vertex_format,
+            voxelization: settings.voxelization,
+            verify: settings.verify,
+            narrow_band: settings.narrow_band.unwrap_or(0),
+            sign: settings.sign,
+            resolution: settings.resolution.clone(),
+            output_format,
+            readback: settings.readback,
+            generation: state.generation.wrapping_add(1),
+            layout_generation,
         };
     }
 }
 
+// cached with the half thickness it was built for
 #[derive(Component)]
-struct StaticObb(Frustum);
+struct StaticObb(Frustum, f32);
 
 trait AabbIntersect {
     fn intersect_min(&self, with: Vec3) -> Aabb;
@@ -131,6 +495,163 @@ impl AabbIntersect for Aabb {
     }
 }
 
+// world space bounds of a local aabb
+fn world_aabb(aabb: &Aabb, g_trans: &GlobalTransform) -> Aabb {
+    let matrix = g_trans.compute_matrix();
+    let center = matrix.transform_point3a(aabb.center);
+    let abs_matrix = Mat3A::from_cols(
+        matrix.x_axis.truncate().abs().into(),
+        matrix.y_axis.truncate().abs().into(),
+        matrix.z_axis.truncate().abs().into(),
+    );
+    let half_extents = abs_matrix * aabb.half_extents;
+    Aabb {
+        center,
+        half_extents,
+    }
+}
+
+fn half_thickness(thickness: Option<&SdfMinThickness>) -> f32 {
+    thickness.map_or(0.0, |thickness| thickness.0.max(0.0) * 0.5)
+}
+
+// grow world space bounds to cover the slabs voxelized for SdfMinThickness
+fn thickened_aabb(aabb: Aabb, thickness: Option<&SdfMinThickness>) -> Aabb {
+    Aabb {
+        center: aabb.center,
+        half_extents: aabb.half_extents + half_thickness(thickness),
+    }
+}
+
+fn aabbs_intersect(a: &Aabb, b: &Aabb) -> bool {
+    (a.center - b.center).abs().cmple(a.half_extents + b.half_extents).all()
+}
+
+impl CascadeState {
+    fn tile_size(&self) -> f32 {
+        self.settings.tile_size(self.tile_dim_count as u32)
+    }
+
+    // add the tiles touched by a world space aabb to the dirty region
+    // returns whether any of the bounds is in the cascade
+    fn mark_dirty(&mut self, bounds: &Aabb) -> bool {
+        let Some(origin) = self.last_origin else {
+            // full redraw pending anyway
+            return false;
+        };
+
+        let tile_size = self.tile_size();
+        let min = (Vec3::from(bounds.min()) / tile_size).floor().as_ivec3().max(origin);
+        let max = ((Vec3::from(bounds.max()) / tile_size).floor().as_ivec3() + 1).min(origin + self.tile_dim_count);
+
+        if !max.cmpgt(min).all() {
+            return false;
+        }
+        self.dirty = Some(match self.dirty {
+            Some((dirty_min, dirty_max)) => (dirty_min.min(min), dirty_max.max(max)),
+            None => (min, max),
+        });
+        true
+    }
+
+    fn target_origin(&self, centre: Vec3) -> IVec3 {
+        ((centre / self.tile_size()) - (self.tile_dim_count as f32 / 2.0))
+            .floor()
+            .as_ivec3()
+    }
+
+    fn has_work(&self, origin: IVec3) -> bool {
+        self.last_origin != Some(origin) || self.dirty.is_some()
+    }
+
+    // score for `SchedulingMode::MostNeedy`, 0 if there is nothing to do
+    fn need(&self, origin: IVec3, centre: Vec3, frame: u32, weights: &NeedWeights) -> f32 {
+        let Some(last_origin) = self.last_origin else {
+            return f32::INFINITY;
+        };
+
+        let tile_size = self.tile_size();
+        let tile_dim_count = self.tile_dim_count;
+
+        // the scrolled-in slab sits at the edge of the cascade
+        let redraw_tiles = (origin - last_origin).abs().min(IVec3::splat(tile_dim_count)).max_element();
+        let scroll_distance = match redraw_tiles {
+            0 => f32::MAX,
+            _ => (tile_dim_count / 2 - redraw_tiles).max(0) as f32 * tile_size,
+        };
+
+        let (dirty_tiles, dirty_distance) = match self.dirty {
+            Some((dirty_min, dirty_max)) => {
+                let size = (dirty_max.min(origin + tile_dim_count) - dirty_min.max(origin)).max(IVec3::ZERO);
+                let min = dirty_min.as_vec3() * tile_size;
+                let max = dirty_max.as_vec3() * tile_size;
+                let distance = (min - centre).max(centre - max).max(Vec3::ZERO).length();
+                ((size.x * size.y * size.z) as f32, distance)
+            }
+            None => (0.0, f32::MAX),
+        };
+
+        if redraw_tiles == 0 && dirty_tiles == 0.0 {
+            return 0.0;
+        }
+
+        let tiles_per_slab = (self.tile_dim_count * self.tile_dim_count) as f32;
+        let frames_since_update = frame.wrapping_sub(self.last_update_frame) as f32;
+        let distance = scroll_distance.min(dirty_distance);
+
+        (weights.redraw * redraw_tiles as f32 + weights.dirty * dirty_tiles / tiles_per_slab)
+            * (1.0 + weights.staleness * frames_since_update)
+            / (1.0 + weights.distance * distance)
+    }
+}
+
+
+// main world tracking of static casters, so we know what to invalidate when they (or their meshes) change
+#[derive(Default)]
+struct StaticCasters {
+    casters: HashMap<Entity, (Aabb, Option<Handle<Mesh>>)>,
+    instances: HashMap<Handle<Mesh>, HashSet<Entity>>,
+    // the filter the casters were picked with
+    filter: Option<ExtractionFilter>,
+}
+
+impl StaticCasters {
+    // returns the previous bounds
+    fn update(&mut self, ent: Entity, bounds: Option<Aabb>, handle: Option<&Handle<Mesh>>) -> Option<Aabb> {
+        let previous = match bounds {
+            Some(bounds) => self.casters.insert(ent, (bounds, handle.map(Handle::clone_weak))),
+            None => self.casters.remove(&ent),
+        };
+
+        let (old_bounds, old_handle) = match previous {
+            Some((bounds, handle)) => (Some(bounds), handle),
+            None => (None, None),
+        };
+
+        if let Some(old_handle) = old_handle {
+            if let Some(instances) = self.instances.get_mut(&old_handle) {
+                instances.remove(&ent);
+                if instances.is_empty() {
+                    self.instances.remove(&old_handle);
+                }
+            }
+        }
+
+        if let Some((_, Some(handle))) = self.casters.get(&ent) {
+            self.instances.entry(handle.clone_weak()).or_default().insert(ent);
+        }
+
+        old_bounds
+    }
+
+    fn instance_bounds<'a>(&'a self, handle: &Handle<Mesh>) -> impl Iterator<Item = &'a Aabb> + 'a {
+        self.instances
+            .get(handle)
+            .into_iter()
+            .flatten()
+            .filter_map(|ent| self.casters.get(ent).map(|(bounds, _)| bounds))
+    }
+}
 
 fn update_cascades(
     mut commands: Commands,
@@ -138,30 +659,169 @@ fn update_cascades(
     centre: Res<SdfCentre>,
     mut state: ResMut<SdfState>,
     frame: Res<FrameCount>,
-    aabbs: Query<(Entity, &Aabb, &GlobalTransform, Option<&StaticObb>, Changed<GlobalTransform>, Changed<Aabb>)>,
+    time: Res<Time>,
+    mut rebuilds: EventReader<SdfRebuild>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    aabbs: Query<(Entity, &Aabb, &GlobalTransform, Option<&StaticObb>, Changed<GlobalTransform>, Changed<Aabb>, Option<&SdfCaster>, Option<&NotSdfCaster>, Option<&SdfDynamic>, Option<&SdfMinThickness>)>,
+    mesh_handles: Query<&Handle<Mesh>>,
+    changed: Query<Entity, Or<(Changed<GlobalTransform>, Changed<Aabb>, Changed<Handle<Mesh>>, Added<SdfCaster>, Added<NotSdfCaster>, Added<SdfDynamic>, Changed<SdfMinThickness>)>>,
+    removed: (
+        RemovedComponents<Aabb>,
+        RemovedComponents<Handle<Mesh>>,
+        RemovedComponents<SdfCaster>,
+        RemovedComponents<NotSdfCaster>,
+        RemovedComponents<SdfDynamic>,
+        RemovedComponents<SdfMinThickness>,
+    ),
+    mut static_casters: Local<StaticCasters>,
 ) {
+    // after a filter change, recheck the static entities it now includes or excludes
+    let mut refiltered = Vec::new();
+    if static_casters.filter != Some(settings.filter) {
+        static_casters.filter = Some(settings.filter);
+        refiltered.extend(aabbs.iter().filter_map(|(ent, _, _, _, _, _, marked, opted_out, dynamic, _)| {
+            let included = dynamic.is_none() && settings.filter.includes(marked.is_some(), opted_out.is_some());
+            (included != static_casters.casters.contains_key(&ent)).then_some(ent)
+        }));
+    }
+
+    // mark the old and new bounds of any changed static casters as dirty
+    let (removed_aabbs, removed_meshes, removed_marked, removed_opted_out, removed_dynamic, removed_thickness) = removed;
+    let recheck = changed
+        .iter()
+        .chain(refiltered)
+        .chain(removed_aabbs.iter())
+        .chain(removed_meshes.iter())
+        .chain(removed_marked.iter())
+        .chain(removed_opted_out.iter())
+        .chain(removed_dynamic.iter())
+        .chain(removed_thickness.iter())
+        .collect::<HashSet<_>>();
+    for ent in recheck {
+        let new_bounds = match aabbs.get(ent) {
+            Ok((_, aabb, g_trans, _, _, _, marked, opted_out, None, thickness)) if settings.filter.includes(marked.is_some(), opted_out.is_some()) => {
+                Some(thickened_aabb(world_aabb(aabb, g_trans), thickness))
+            }
+            _ => None,
+        };
+        let new_handle = new_bounds.as_ref().and_then(|_| mesh_handles.get(ent).ok());
+
+        let old_bounds = static_casters.update(ent, new_bounds.clone(), new_handle);
+
+        for bounds in old_bounds.iter().chain(new_bounds.iter()) {
+            for cascade in state.cascades.iter_mut() {
+                cascade.mark_dirty(bounds);
+            }
+        }
+    }
+
+    // mark the bounds of all static instances of changed mesh assets as dirty
+    for event in mesh_events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } | AssetEvent::Removed { handle } => handle,
+        };
+
+        for bounds in static_casters.instance_bounds(handle) {
+            for cascade in state.cascades.iter_mut() {
+                cascade.mark_dirty(bounds);
+            }
+        }
+    }
+
+    for rebuild in rebuilds.iter() {
+        for (i, cascade) in state.cascades.iter_mut().enumerate() {
+            match rebuild {
+                SdfRebuild::Region(bounds) => cascade.dirty_requested |= cascade.mark_dirty(bounds),
+                SdfRebuild::Cascade(index) if *index != i => (),
+                _ => {
+                    // full redraw
+                    cascade.last_origin = None;
+                    cascade.dirty = None;
+                    cascade.dirty_requested = false;
+                }
+            }
+        }
+    }
+
+    // pick the cascade to update this frame
+    let candidates = settings.cascades.iter().zip(&state.cascades).enumerate().filter_map(|(i, (cascade, state))| {
+        let origin = state.target_origin(centre.0);
+        let since_last_update = time.elapsed().saturating_sub(state.last_update_time);
+        let due = state.last_origin.is_none() || state.dirty_requested || match (&settings.scheduling, &cascade.update_schedule) {
+            (SchedulingMode::MostNeedy(_), CascadeUpdateSchedule::Frames { .. }) => true,
+            (_, schedule) => schedule.is_due(frame.0, since_last_update, state.last_origin != Some(origin)),
+        };
+        (due && state.has_work(origin)).then_some((i, origin))
+    });
+    let selected = match &settings.scheduling {
+        SchedulingMode::Fixed => candidates.map(|(i, _)| i).next(),
+        SchedulingMode::MostNeedy(weights) => candidates
+            .map(|(i, origin)| (i, state.cascades[i].need(origin, centre.0, frame.0, weights)))
+            .filter(|(_, score)| *score > 0.0)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i),
+    };
+
     let mut clip_rects = Vec::new();
-    let mut updated = false;
     // clip_rects.push(Aabb::from_min_max(Vec3::splat(f32::MIN), Vec3::splat(f32::MAX)));
 
-    for (_i, (cascade, state)) in settings.cascades.iter().zip(&mut state.cascades).enumerate() {
-        if frame.0 % cascade.update_schedule.frequency == cascade.update_schedule.offset && !updated {
-            let tile_size = cascade.tile_size();
-            let origin = ((centre.0 / tile_size) - (TILE_DIM_COUNT as f32 / 2.0))
-                .floor()
-                .as_ivec3();
+    for (i, state) in state.cascades.iter_mut().enumerate() {
+        state.update_region = None;
+
+        if selected == Some(i) {
+            let tile_size = state.tile_size();
+            let origin = state.target_origin(centre.0);
 
             state.redraw = match state.last_origin {
-                Some(last_origin) => (origin - last_origin).min(IVec3::splat(TILE_DIM_COUNT as i32)).max(IVec3::splat(TILE_DIM_COUNT as i32 * -1)),
-                None => IVec3::new(TILE_DIM_COUNT as i32, 0, 0),
+                Some(last_origin) => (origin - last_origin).min(IVec3::splat(state.tile_dim_count)).max(IVec3::splat(state.tile_dim_count * -1)),
+                None => IVec3::new(state.tile_dim_count, 0, 0),
 
             };
 
             if state.redraw == IVec3::ZERO {
+                // no scroll required, rebuild any dirty region in place
+                state.dirty_requested = false;
+                let Some((dirty_min, dirty_max)) = state.dirty.take() else {
+                    continue;
+                };
+
+                let border_min = origin.as_vec3() * tile_size;
+                let border_max = (origin + state.tile_dim_count).as_vec3() * tile_size;
+                let mut clip_rect = Aabb::from_min_max(border_min, border_max)
+                    .intersect_min(dirty_min.as_vec3() * tile_size)
+                    .intersect_max(dirty_max.as_vec3() * tile_size);
+
+                let mut local_min = (dirty_min - origin).max(IVec3::ZERO);
+                let mut local_max = (dirty_max - origin).min(IVec3::splat(state.tile_dim_count));
+                if !local_max.cmpgt(local_min).all() {
+                    continue;
+                }
+
+                if settings.sign != SdfSign::Unsigned {
+                    // signs are counted along z columns, so rebuild the whole column
+                    local_min.z = 0;
+                    local_max.z = state.tile_dim_count;
+                    clip_rect = Aabb::from_min_max(
+                        Vec3::from(clip_rect.min()).truncate().extend(border_min.z),
+                        Vec3::from(clip_rect.max()).truncate().extend(border_max.z),
+                    );
+                }
+
+                state.last_update_frame = frame.0;
+                state.last_update_time = time.elapsed();
+                state.update_region = Some((local_min, local_max));
+                clip_rects.push(clip_rect);
                 continue;
             }
 
-            updated = true;
+            state.last_update_frame = frame.0;
+            state.last_update_time = time.elapsed();
+
+            if state.last_origin.is_none() {
+                // full redraw covers anything dirty
+                state.dirty = None;
+                state.dirty_requested = false;
+            }
 
             match state.last_origin.as_mut() {
                 Some(last_origin) => {
@@ -182,8 +842,15 @@ fn update_cascades(
                 _ => state.last_origin = Some(origin),
             }
 
+            // region to revoxelize, the slab we scrolled into
+            let tile_dim_count = IVec3::splat(state.tile_dim_count);
+            state.update_region = Some((
+                IVec3::select(state.redraw.cmpgt(IVec3::ZERO), tile_dim_count - state.redraw, IVec3::ZERO),
+                IVec3::select(state.redraw.cmplt(IVec3::ZERO), -state.redraw, tile_dim_count),
+            ));
+
             let border_min = origin.as_vec3() * tile_size;
-            let border_max = (origin + TILE_DIM_COUNT as i32).as_vec3() * tile_size;
+            let border_max = (origin + state.tile_dim_count).as_vec3() * tile_size;
 
             let border_aabb = Aabb::from_min_max(border_min, border_max);
 
@@ -240,12 +907,24 @@ fn update_cascades(
     }
 
     state.visible_entities.clear();
+    state.dynamic_entities.clear();
+    let mut dynamic_casters = Vec::new();
     let mut _count = 0;
     let mut _total = 0;
-    if !clip_rects.is_empty() {
-        for (ent, aabb, g_trans, maybe_obb, changed_trans, changed_aabb) in &aabbs {
+    for (ent, aabb, g_trans, maybe_obb, changed_trans, changed_aabb, marked, opted_out, dynamic, thickness) in &aabbs {
+        if !settings.filter.includes(marked.is_some(), opted_out.is_some()) {
+            continue;
+        }
+
+        if dynamic.is_some() {
+            dynamic_casters.push((ent, thickened_aabb(world_aabb(aabb, g_trans), thickness)));
+            continue;
+        }
+
+        if !clip_rects.is_empty() {
+            let half_thickness = half_thickness(thickness);
             let obb = match (maybe_obb, changed_trans || changed_aabb) {
-                (Some(obb), false) => obb.0.clone(),
+                (Some(obb), false) if obb.1 == half_thickness => obb.0.clone(),
                 _ => {
                     let planes = [-Vec3A::X, Vec3A::X, -Vec3A::Y, Vec3A::Y, -Vec3A::Z, Vec3A::Z]
                         .into_iter()
@@ -253,15 +932,16 @@ fn update_cascades(
                             let matrix = g_trans.compute_matrix();
                             let point_on_plane = aabb.center + v * aabb.half_extents;
                             let transformed_point = (matrix * point_on_plane.extend(1.0)).truncate();
-                            let transformed_normal = (matrix * -v.extend(0.0)).truncate();
-                            Plane::new(transformed_normal.extend(-transformed_point.dot(transformed_normal)))
+                            let transformed_normal = (matrix * -v.extend(0.0)).truncate().normalize();
+                            // pushed out by the thickness
+                            Plane::new(transformed_normal.extend(-transformed_point.dot(transformed_normal) + half_thickness))
                         })
                         .collect::<Vec<_>>()
                         .try_into()
                         .unwrap();
                     let frustum = Frustum { planes };
 
-                    commands.entity(ent).insert(StaticObb(frustum.clone()));
+                    commands.entity(ent).insert(StaticObb(frustum.clone(), half_thickness));
                     frustum
                 },
             };
@@ -291,22 +971,61 @@ fn update_cascades(
     // if _count > 0 {
     //     println!("inc {}/{}", _count, _total);
     // }
+
+    // dynamic layer is redrawn in full, for any number of cascades per frame
+    for (cascade, state) in settings.cascades.iter().zip(&mut state.cascades) {
+        state.dynamic_redraw = false;
+
+        let Some(origin) = state.last_origin else {
+            continue;
+        };
+
+        let tile_size = state.tile_size();
+        let border_min = origin.as_vec3() * tile_size;
+        let border_max = (origin + state.tile_dim_count).as_vec3() * tile_size;
+        let border_aabb = Aabb::from_min_max(border_min, border_max);
+
+        let occupied = dynamic_casters.iter().any(|(_, bounds)| aabbs_intersect(bounds, &border_aabb));
+        let needs_clear = occupied || state.dynamic_occupied;
+
+        // if the static layer scrolled we must redraw the dynamic layer too, since the scrolled-in region holds stale data
+        let since_last_update = time.elapsed().saturating_sub(state.last_dynamic_update_time);
+        let scrolled = state.redraw != IVec3::ZERO;
+        if needs_clear && (cascade.dynamic_update_schedule.is_due(frame.0, since_last_update, scrolled) || scrolled) {
+            state.dynamic_redraw = true;
+            state.last_dynamic_update_time = time.elapsed();
+            state.dynamic_occupied = occupied;
+        }
+    }
+
+    if state.cascades.iter().any(|cascade| cascade.dynamic_redraw) {
+        state.dynamic_entities.extend(dynamic_casters.into_iter().map(|(ent, _)| ent));
+    }
 }
 
 #[derive(Component)]
 struct SdfSceneData {
     transform: Mat4,
     handle: Handle<Mesh>,
+    dynamic: bool,
+    half_thickness: f32,
 }
 
 fn extract_sdf_meshes(
     mut commands: Commands,
     state: Extract<Res<SdfState>>,
-    meshes_query: Extract<Query<(&GlobalTransform, &Handle<Mesh>)>>,
+    settings: Extract<Res<SceneSdfSettings>>,
+    meshes_query: Extract<Query<(&GlobalTransform, &Handle<Mesh>, Option<&SdfCaster>, Option<&NotSdfCaster>, Option<&SdfMinThickness>)>>,
 ) {
     let mut sdf_mesh_cmds = Vec::new();
-    for ent in &state.visible_entities {
-        if let Ok((transform, handle)) = meshes_query.get(*ent) {
+    let static_entities = state.visible_entities.iter().map(|ent| (ent, false));
+    let dynamic_entities = state.dynamic_entities.iter().map(|ent| (ent, true));
+    for (ent, dynamic) in static_entities.chain(dynamic_entities) {
+        if let Ok((transform, handle, marked, opted_out, thickness)) = meshes_query.get(*ent) {
+            if !settings.filter.includes(marked.is_some(), opted_out.is_some()) {
+                continue;
+            }
+
             let transform = transform.compute_matrix();
 
             sdf_mesh_cmds.push((
@@ -314,6 +1033,8 @@ fn extract_sdf_meshes(
                 (SdfSceneData {
                     transform,
                     handle: handle.clone_weak(),
+                    dynamic,
+                    half_thickness: half_thickness(thickness),
                 }),
             ));
         }
@@ -322,6 +1043,15 @@ fn extract_sdf_meshes(
     commands.insert_or_spawn_batch(sdf_mesh_cmds);
 }
 
+struct StaticUpdate {
+    // cascade header offsets for the update region and for the whole cascade
+    offset: u32,
+    output_offset: u32,
+    redraw: IVec3,
+    // workgroups, see stitch_region
+    stitch_count: Option<UVec3>,
+}
+
 #[derive(Resource)]
 struct SdfWorkingData {
     // timestamps
@@ -331,23 +1061,65 @@ struct SdfWorkingData {
     cascade_header_buffer: DynamicUniformBuffer<SdfCascadeInfo>,
     mesh_header_buffer: StorageBuffer<SdfMeshHeader>,
     transforms_buffer: StorageBuffer<SdfTransforms>,
-    tris_buffer: StorageBuffer<Vec<Vec4>>,
+    dynamic_mesh_header_buffer: StorageBuffer<SdfMeshHeader>,
+    dynamic_transforms_buffer: StorageBuffer<SdfTransforms>,
+    indices: MeshAllocator<u32>,
+    // packed according to the mesh's vertex format
+    vertices: MeshAllocator<u32>,
+    // vertex format and dequantization transform per mesh
+    mesh_formats: HashMap<Handle<Mesh>, (SdfVertexFormat, Mat4)>,
     // intermediate buffers
-    coarse_tri_buffer: StorageBuffer<Vec<ProcessedTriData>>, // max tri count
-    coarse_counts_per_tile: StorageBuffer<Vec<UVec2>>,         // tile count
-    coarse_tile_ids: StorageBuffer<Vec<u32>>,                // max tile id count
+    transformed_tris: Option<(Buffer, usize)>,                // largest tri count seen
+    coarse_counts_per_tile: Option<Buffer>,                    // max tiles
+    coarse_tile_ids: Option<Buffer>,                           // max tile id count
+    // resolution the buffers and textures were allocated for
+    resolution: Option<SdfResolution>,
+    // format of the output texture
+    output_format: Option<SdfOutputFormat>,
     dispatch_fine_tiles: Option<Buffer>,
+    coarse_bins: Option<Buffer>,
     fine_tile_output: Option<Buffer>,
-    jfa_buffers: Option<(Texture, TextureView, Texture, TextureView, usize)>,
+    jfa_buffers: Option<JfaTextures>,
+    dynamic_jfa_buffers: Option<JfaTextures>,
+    // per voxel surface crossings then interior bits, followed by a leaky bit per column, see parity.wgsl
+    sign_bits: Option<Buffer>,
     jfa_params: DynamicUniformBuffer<JfaParams>,
     // params
-    mesh_offsets: HashMap<Handle<Mesh>, (usize, usize)>,
     jfa_param_offsets: Vec<u32>,
+    // passes to run this frame
+    static_updates: Vec<StaticUpdate>,
+    // (cascade header offset, redraw)
+    dynamic_updates: Vec<(u32, IVec3)>,
+    // whole cascade header offsets of every cascade that updates. static updates output their own cascade
+    output_updates: Vec<u32>,
+    static_tri_count: u32,
+    dynamic_tri_count: u32,
     // bindgroups
     jfa_bindgroup: Option<BindGroup>,
     working_bindgroup: Option<BindGroup>,
+    dynamic_working_bindgroup: Option<BindGroup>,
     dispatch_bindgroup: Option<BindGroup>,
-    output_bindgroups: Vec<BindGroup>,
+    output_bindgroup: Option<BindGroup>,
+    // brute force verification, see SceneSdfSettings::verify
+    verify: Option<SdfVerifyData>,
+    verify_this_frame: bool,
+}
+
+// shared by the verify results and the output readback
+enum ReadbackState {
+    Idle,
+    // results copied to the readback buffer, waiting to be mapped
+    Copied,
+    Mapping,
+}
+
+struct SdfVerifyData {
+    results: Buffer,
+    readback: Buffer,
+    bindgroup: BindGroup,
+    state: ReadbackState,
+    mapped: Arc<AtomicBool>,
+    map_failed: Arc<AtomicBool>,
 }
 
 impl Default for SdfWorkingData {
@@ -358,26 +1130,36 @@ impl Default for SdfWorkingData {
             cascade_header_buffer: Default::default(),
             mesh_header_buffer: Default::default(),
             transforms_buffer: Default::default(),
-            tris_buffer: Default::default(),
-            coarse_tri_buffer: StorageBuffer::from(Vec::from_iter(
-                std::iter::repeat(Default::default()).take(MAX_TRI_COUNT),
-            )),
-            coarse_counts_per_tile: StorageBuffer::from(Vec::from_iter(
-                std::iter::repeat(Default::default()).take(MAX_TILES),
-            )),
-            coarse_tile_ids: StorageBuffer::from(Vec::from_iter(
-                std::iter::repeat(Default::default()).take(MAX_ID_COUNT),
-            )),
-            mesh_offsets: Default::default(),
+            dynamic_mesh_header_buffer: Default::default(),
+            dynamic_transforms_buffer: Default::default(),
+            indices: MeshAllocator::new("sdf mesh indices"),
+            vertices: MeshAllocator::new("sdf mesh vertices"),
+            mesh_formats: Default::default(),
+            transformed_tris: None,
+            static_tri_count: 0,
+            dynamic_tri_count: 0,
+            coarse_counts_per_tile: Default::default(),
+            coarse_tile_ids: Default::default(),
+            resolution: None,
+            output_format: None,
+            static_updates: Default::default(),
+            dynamic_updates: Default::default(),
+            output_updates: Default::default(),
             working_bindgroup: Default::default(),
+            dynamic_working_bindgroup: Default::default(),
             dispatch_bindgroup: Default::default(),
             fine_tile_output: Default::default(),
             jfa_buffers: Default::default(),
+            dynamic_jfa_buffers: Default::default(),
             jfa_params: Default::default(),
             jfa_param_offsets: Default::default(),
             jfa_bindgroup: Default::default(),
-            output_bindgroups: Default::default(),
+            output_bindgroup: Default::default(),
+            verify: None,
+            verify_this_frame: false,
             dispatch_fine_tiles: None,
+            coarse_bins: None,
+            sign_bits: None,
         }
     }
 }
@@ -388,9 +1170,251 @@ pub struct SdfOutputData {
     pub output_buffer: Option<(Texture, TextureView, usize)>,
 }
 
+// a cascade read back from the output texture, for SceneSdf
+struct SdfReadback {
+    generation: u32,
+    cascade: usize,
+    mirror: MirroredCascade,
+}
+
+#[derive(Resource)]
+struct SdfReadbackReceiver(Mutex<Receiver<SdfReadback>>);
+
+fn receive_sdf_readback(
+    state: Res<SdfState>,
+    receiver: Res<SdfReadbackReceiver>,
+    mut scene_sdf: ResMut<SceneSdf>,
+) {
+    if scene_sdf.generation != state.layout_generation {
+        scene_sdf.reset(state.layout_generation, state.resolution.voxels_per_dim(), state.cascades.len());
+    }
+
+    let receiver = receiver.0.lock().unwrap();
+    while let Ok(readback) = receiver.try_recv() {
+        // anything older was read back for a previous layout
+        if readback.generation == state.layout_generation {
+            scene_sdf.set_cascade(readback.cascade, readback.mirror);
+        }
+    }
+}
+
+// async copies of the output texture, with one copy per cascade in flight at a time
+#[derive(Resource)]
+struct SdfReadbackData {
+    sender: Mutex<Sender<SdfReadback>>,
+    // SdfState::layout_generation the buffers were made for
+    generation: Option<u32>,
+    bytes_per_row: u32,
+    cascades: Vec<CascadeReadback>,
+    // cascades to copy out of the output texture this frame
+    copies: Vec<usize>,
+}
+
+struct CascadeReadback {
+    buffer: Buffer,
+    state: ReadbackState,
+    mapped: Arc<AtomicBool>,
+    map_failed: Arc<AtomicBool>,
+    // updated since the last copy
+    stale: bool,
+    // where the copied data sits, taken when the copy is queued
+    copied: Option<(Vec3, f32, f32)>,
+}
+
+impl SdfReadbackData {
+    fn new(sender: Sender<SdfReadback>) -> Self {
+        Self {
+            sender: Mutex::new(sender),
+            generation: None,
+            bytes_per_row: 0,
+            cascades: Vec::new(),
+            copies: Vec::new(),
+        }
+    }
+}
+
+fn output_texel_size(format: SdfOutputFormat) -> u32 {
+    match format {
+        SdfOutputFormat::R32Float => 4,
+        SdfOutputFormat::R16Float => 2,
+        SdfOutputFormat::R8Unorm | SdfOutputFormat::R8Snorm => 1,
+    }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+// world unit distances from a padded copy of one cascade, see sdf::encoding
+fn decode_readback(bytes: &[u8], bytes_per_row: u32, voxels_per_dim: u32, format: SdfOutputFormat, band: f32) -> Vec<f32> {
+    let v = voxels_per_dim as usize;
+    let texel_size = output_texel_size(format) as usize;
+    let mut distances = Vec::with_capacity(v * v * v);
+    for row in bytes.chunks(bytes_per_row as usize).take(v * v) {
+        distances.extend(row[..v * texel_size].chunks(texel_size).map(|texel| match format {
+            SdfOutputFormat::R32Float => f32::from_le_bytes([texel[0], texel[1], texel[2], texel[3]]),
+            SdfOutputFormat::R16Float => f16_to_f32(u16::from_le_bytes([texel[0], texel[1]])),
+            SdfOutputFormat::R8Unorm => (texel[0] as f32 / 255.0 * 2.0 - 1.0) * band,
+            SdfOutputFormat::R8Snorm => (texel[0] as i8 as f32 / 127.0).max(-1.0) * band,
+        }));
+    }
+    distances
+}
+
+fn queue_sdf_readback(
+    state: Res<SdfState>,
+    output_data: Res<SdfOutputData>,
+    device: Res<RenderDevice>,
+    mut readback: ResMut<SdfReadbackData>,
+) {
+    readback.copies.clear();
+    if !state.readback || output_data.output_buffer.is_none() {
+        readback.generation = None;
+        readback.cascades.clear();
+        return;
+    }
+
+    let voxels_per_dim = state.resolution.voxels_per_dim();
+    if readback.generation != Some(state.layout_generation) {
+        // anything in flight is for the old layout, and is dropped with its buffer
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let bytes_per_row = (voxels_per_dim * output_texel_size(state.output_format) + alignment - 1) / alignment * alignment;
+        readback.bytes_per_row = bytes_per_row;
+        readback.cascades = state.cascades.iter().map(|_| CascadeReadback {
+            buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("sdf output readback"),
+                size: bytes_per_row as u64 * voxels_per_dim as u64 * voxels_per_dim as u64,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            state: ReadbackState::Idle,
+            mapped: Default::default(),
+            map_failed: Default::default(),
+            // the texture is already written for cascades that updated since the last layout change, or before
+            // readback was turned on
+            stale: true,
+            copied: None,
+        }).collect();
+        readback.generation = Some(state.layout_generation);
+    }
+
+    let readback = &mut *readback;
+    for (i, (cascade, cascade_readback)) in state.cascades.iter().zip(readback.cascades.iter_mut()).enumerate() {
+        cascade_readback.stale |= cascade.update_region.is_some() || cascade.dynamic_redraw;
+
+        match cascade_readback.state {
+            ReadbackState::Idle => {
+                let Some(origin) = cascade.last_origin.filter(|_| cascade_readback.stale) else {
+                    continue;
+                };
+
+                let tile_size = cascade.tile_size();
+                let voxel_size = tile_size / state.resolution.voxels_per_tile_dim as f32;
+                let band = state.truncation(cascade) as f32 * voxel_size;
+                cascade_readback.copied = Some((origin.as_vec3() * tile_size, voxel_size, band));
+                cascade_readback.stale = false;
+                cascade_readback.state = ReadbackState::Copied;
+                readback.copies.push(i);
+            }
+            ReadbackState::Copied => {
+                let (mapped, map_failed) = (cascade_readback.mapped.clone(), cascade_readback.map_failed.clone());
+                device.map_buffer(&cascade_readback.buffer.slice(..), wgpu::MapMode::Read, move |res| {
+                    match res {
+                        Ok(_) => mapped.store(true, Ordering::Release),
+                        Err(e) => {
+                            error!("sdf readback: failed to map output: {e}");
+                            map_failed.store(true, Ordering::Release);
+                        }
+                    }
+                });
+                cascade_readback.state = ReadbackState::Mapping;
+            }
+            ReadbackState::Mapping => {
+                if cascade_readback.map_failed.swap(false, Ordering::Acquire) {
+                    // the buffer was never mapped, copy again
+                    cascade_readback.state = ReadbackState::Idle;
+                    cascade_readback.stale = true;
+                } else if cascade_readback.mapped.swap(false, Ordering::Acquire) {
+                    let (min, voxel_size, band) = cascade_readback.copied.unwrap();
+                    let range = cascade_readback.buffer.slice(..).get_mapped_range();
+                    let distances = decode_readback(&range, readback.bytes_per_row, voxels_per_dim, state.output_format, band);
+                    drop(range);
+                    cascade_readback.buffer.unmap();
+                    cascade_readback.state = ReadbackState::Idle;
+
+                    // the receiver only goes away with the app
+                    let _ = readback.sender.lock().unwrap().send(SdfReadback {
+                        generation: state.layout_generation,
+                        cascade: i,
+                        mirror: MirroredCascade {
+                            min,
+                            voxel_size,
+                            band,
+                            distances: distances.into(),
+                        },
+                    });
+                }
+            }
+        }
+    }
+}
+
+// copies the cascades picked by queue_sdf_readback, after SceneSdfNode has written them
+struct SdfReadbackNode;
+
+impl Node for SdfReadbackNode {
+    fn run(
+        &self,
+        _graph: &mut bevy::render::render_graph::RenderGraphContext,
+        render_context: &mut bevy::render::renderer::RenderContext,
+        world: &World,
+    ) -> Result<(), bevy::render::render_graph::NodeRunError> {
+        let readback = world.resource::<SdfReadbackData>();
+        let Some((texture, ..)) = world.resource::<SdfOutputData>().output_buffer.as_ref() else {
+            return Ok(());
+        };
+
+        let voxels_per_dim = world.resource::<SdfState>().resolution.voxels_per_dim();
+        for cascade in readback.copies.iter() {
+            render_context.command_encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: *cascade as u32 * voxels_per_dim, y: 0, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &readback.cascades[*cascade].buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(readback.bytes_per_row),
+                        rows_per_image: NonZeroU32::new(voxels_per_dim),
+                    },
+                },
+                Extent3d {
+                    width: voxels_per_dim,
+                    height: voxels_per_dim,
+                    depth_or_array_layers: voxels_per_dim,
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(ShaderType, Default, Clone)]
-struct ProcessedTriData {
+struct TransformedTriData {
     vertex_positions: [Vec4; 3],
+    half_thickness: f32,
 }
 
 fn prepare_sdf_meshes(
@@ -398,84 +1422,86 @@ fn prepare_sdf_meshes(
     queue: Res<RenderQueue>,
     extracted_assets: Res<ExtractedAssets<Mesh>>,
     mut pos_data: ResMut<SdfWorkingData>,
+    mut output_data: ResMut<SdfOutputData>,
+    state: Res<SdfState>,
     mut init: Local<bool>,
-    mut state: ResMut<SdfState>,
 ) {
     if !*init {
         *init = true;
         pos_data.cascade_header_buffer.write_buffer(&device, &queue);
         pos_data.mesh_header_buffer.write_buffer(&device, &queue);
         pos_data.transforms_buffer.write_buffer(&device, &queue);
-        pos_data.tris_buffer.write_buffer(&device, &queue);
-        pos_data.coarse_tri_buffer.write_buffer(&device, &queue);
-        pos_data
-            .coarse_counts_per_tile
-            .write_buffer(&device, &queue);
-        pos_data.coarse_tile_ids.write_buffer(&device, &queue);
+        pos_data.dynamic_mesh_header_buffer.write_buffer(&device, &queue);
+        pos_data.dynamic_transforms_buffer.write_buffer(&device, &queue);
     }
 
-    if extracted_assets.removed.is_empty() && extracted_assets.extracted.is_empty() {
-        return;
+    // (re)allocate everything sized by the resolution, the rest is recreated in queue_sdf_data
+    if pos_data.resolution.as_ref() != Some(&state.resolution) {
+        pos_data.coarse_counts_per_tile = None;
+        pos_data.coarse_tile_ids = None;
+        pos_data.coarse_bins = None;
+        pos_data.fine_tile_output = None;
+        pos_data.sign_bits = None;
+        pos_data.jfa_buffers = None;
+        pos_data.dynamic_jfa_buffers = None;
+        output_data.output_buffer = None;
+        pos_data.resolution = Some(state.resolution.clone());
     }
 
-    if !extracted_assets.removed.is_empty() {
-        let prev_buffer = std::mem::take(&mut pos_data.tris_buffer);
-        let prev_data = prev_buffer.get();
-
-        let mut new_vec = Vec::new();
-
-        let mut running_offset = 0;
-        let offsets = &mut pos_data.mesh_offsets;
-        offsets.retain(|handle, (offset, count)| {
-            if extracted_assets.removed.contains(handle) {
-                false
-            } else {
-                new_vec.extend_from_slice(&prev_data[*offset..*offset + *count]);
-                *offset = running_offset;
-                running_offset += *count;
-                true
-            }
-        });
+    if pos_data.output_format != Some(state.output_format) {
+        output_data.output_buffer = None;
+        pos_data.output_format = Some(state.output_format);
+    }
 
-        pos_data.tris_buffer.set(new_vec);
+    for handle in &extracted_assets.removed {
+        pos_data.indices.remove(handle);
+        pos_data.vertices.remove(handle);
+        pos_data.mesh_formats.remove(handle);
     }
 
-    let mut offsets = std::mem::take(&mut pos_data.mesh_offsets);
-    let data = pos_data.tris_buffer.get_mut();
+    // modified meshes replace their previous allocation
     for (handle, mesh) in &extracted_assets.extracted {
-        if let Some(VertexAttributeValues::Float32x3(positions)) =
-            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
-        {
-            let offset = data.len();
-            let count = match mesh.indices() {
-                Some(indices) => {
-                    data.extend(
-                        indices
-                            .iter()
-                            .map(|ix| Vec3::from_array(positions[ix]).extend(1.0)),
-                    );
-                    indices.len()
-                }
-                None => {
-                    data.extend(
-                        positions
-                            .iter()
-                            .map(|pos| Vec3::from_array(*pos).extend(1.0)),
-                    );
-                    positions.len()
-                }
-            };
+        let (Some(VertexAttributeValues::Float32x3(positions)), Some(aabb)) = (mesh.attribute(Mesh::ATTRIBUTE_POSITION), mesh.compute_aabb()) else {
+            pos_data.indices.remove(handle);
+            pos_data.vertices.remove(handle);
+            pos_data.mesh_formats.remove(handle);
+            continue;
+        };
 
-            offsets.insert(handle.clone_weak(), (offset, count));
-        }
-    }
-    pos_data.mesh_offsets = offsets;
+        let indices: Vec<u32> = match mesh.indices() {
+            Some(indices) => indices.iter().map(|ix| ix as u32).collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
 
-    pos_data.tris_buffer.write_buffer(&device, &queue);
+        let (vertices, dequantize): (Vec<u32>, Mat4) = match state.vertex_format {
+            SdfVertexFormat::Float32 => (
+                positions.iter().flatten().map(|f| f.to_bits()).collect(),
+                Mat4::IDENTITY,
+            ),
+            SdfVertexFormat::Unorm16 => {
+                let min = Vec3::from(aabb.min());
+                let extent = Vec3::from(aabb.half_extents * 2.0).max(Vec3::splat(f32::EPSILON));
+                let vertices = positions
+                    .iter()
+                    .flat_map(|pos| {
+                        let q = ((Vec3::from_array(*pos) - min) / extent * 65535.0).round().as_uvec3().min(UVec3::splat(65535));
+                        [q.x | (q.y << 16), q.z]
+                    })
+                    .collect();
+                (vertices, Mat4::from_translation(min) * Mat4::from_scale(extent))
+            }
+        };
 
-    for cascade in &mut state.cascades {
-        cascade.last_origin = None;
+        pos_data.indices.insert(handle, &indices);
+        pos_data.vertices.insert(handle, &vertices);
+        pos_data.mesh_formats.insert(handle.clone_weak(), (state.vertex_format, dequantize));
     }
+
+    // cascades containing instances of the changed meshes are invalidated in `update_cascades`
+    pos_data.indices.defragment();
+    pos_data.indices.write_buffer(&device, &queue);
+    pos_data.vertices.defragment();
+    pos_data.vertices.write_buffer(&device, &queue);
 }
 
 #[derive(Resource)]
@@ -484,19 +1510,43 @@ struct SceneSdfPipeline {
     shaders: HashSet<Handle<Shader>>,
     working_layout: BindGroupLayout,
     dispatch_layout: BindGroupLayout,
-    coarse_pipeline: CachedComputePipelineId,
+    transform_pipeline: CachedComputePipelineId,
+    coarse_clear_pipeline: CachedComputePipelineId,
+    coarse_count_pipeline: CachedComputePipelineId,
+    coarse_scan_pipeline: CachedComputePipelineId,
+    coarse_write_pipeline: CachedComputePipelineId,
     fine_pipeline: CachedComputePipelineId,
     fine_blend_pipeline: CachedComputePipelineId,
     jfa_layout: BindGroupLayout,
     jfa_pipeline: CachedComputePipelineId,
     stitch_pipeline: CachedComputePipelineId,
     output_layout: BindGroupLayout,
+    verify_layout: BindGroupLayout,
     output_pipeline: CachedComputePipelineId,
+    refine_pipeline: CachedComputePipelineId,
+    sign_clear_pipeline: CachedComputePipelineId,
+    sign_scan_pipeline: CachedComputePipelineId,
+    sign_resolve_pipeline: CachedComputePipelineId,
+    verify_pipeline: CachedComputePipelineId,
+    // the texture formats and shader defs depend on the resolution and output format
+    resolution: SdfResolution,
+    output_format: SdfOutputFormat,
 }
 
-impl FromWorld for SceneSdfPipeline {
-    fn from_world(world: &mut World) -> Self {
+// rebuild the layouts and pipelines when the resolution or output format changes
+fn prepare_sdf_pipeline(world: &mut World) {
+    let state = world.resource::<SdfState>();
+    let (resolution, output_format) = (state.resolution.clone(), state.output_format);
+    if world.get_resource::<SceneSdfPipeline>().map(|pipeline| (&pipeline.resolution, pipeline.output_format)) != Some((&resolution, output_format)) {
+        let pipeline = SceneSdfPipeline::new(world, &resolution, output_format);
+        world.insert_resource(pipeline);
+    }
+}
+
+impl SceneSdfPipeline {
+    fn new(world: &mut World, resolution: &SdfResolution, output_format: SdfOutputFormat) -> Self {
         let device = world.resource::<RenderDevice>();
+        let shader_defs = shader_defs(resolution, output_format);
 
         let entries = [
             // meshes
@@ -521,14 +1571,14 @@ impl FromWorld for SceneSdfPipeline {
                 },
                 count: None,
             },
-            // positions
+            // vertices
             BindGroupLayoutEntry {
                 binding: 2,
                 visibility: ShaderStages::COMPUTE,
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Storage { read_only: true },
                     has_dynamic_offset: false,
-                    min_binding_size: Some(<[f32; 4]>::min_size()),
+                    min_binding_size: Some(u32::min_size()),
                 },
                 count: None,
             },
@@ -550,10 +1600,7 @@ impl FromWorld for SceneSdfPipeline {
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Storage { read_only: false },
                     has_dynamic_offset: false,
-                    min_binding_size: Some(
-                        ProcessedTriData::min_size()
-                            .saturating_mul(NonZeroU64::try_from(MAX_TRI_COUNT as u64).unwrap()),
-                    ),
+                    min_binding_size: Some(TransformedTriData::min_size()),
                 },
                 count: None,
             },
@@ -564,10 +1611,8 @@ impl FromWorld for SceneSdfPipeline {
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Storage { read_only: false },
                     has_dynamic_offset: false,
-                    min_binding_size: Some(
-                        UVec2::min_size()
-                            .saturating_mul(NonZeroU64::try_from(MAX_TILES as u64).unwrap()),
-                    ),
+                    // sized by SdfResolution::max_tiles
+                    min_binding_size: Some(UVec2::min_size()),
                 },
                 count: None,
             },
@@ -578,10 +1623,8 @@ impl FromWorld for SceneSdfPipeline {
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Storage { read_only: false },
                     has_dynamic_offset: false,
-                    min_binding_size: Some(
-                        u32::min_size()
-                            .saturating_mul(NonZeroU64::try_from(MAX_ID_COUNT as u64).unwrap()),
-                    ),
+                    // sized by SdfResolution::max_id_count
+                    min_binding_size: Some(u32::min_size()),
                 },
                 count: None,
             },
@@ -591,7 +1634,7 @@ impl FromWorld for SceneSdfPipeline {
                 visibility: ShaderStages::COMPUTE,
                 ty: BindingType::StorageTexture {
                     access: StorageTextureAccess::ReadWrite,
-                    format: TextureFormat::Rg32Uint,
+                    format: seed_format(resolution),
                     view_dimension: TextureViewDimension::D3,
                 },
                 count: None,
@@ -602,7 +1645,7 @@ impl FromWorld for SceneSdfPipeline {
                 visibility: ShaderStages::COMPUTE,
                 ty: BindingType::StorageTexture {
                     access: StorageTextureAccess::ReadWrite,
-                    format: TextureFormat::Rgba8Sint,
+                    format: nearest_format(resolution),
                     view_dimension: TextureViewDimension::D3,
                 },
                 count: None,
@@ -614,7 +1657,42 @@ impl FromWorld for SceneSdfPipeline {
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Storage { read_only: false },
                     has_dynamic_offset: false,
-                    min_binding_size: NonZeroU64::new((MAX_TILES * std::mem::size_of::<u32>() * 2 * VOXELS_PER_TILE_DIM * VOXELS_PER_TILE_DIM * VOXELS_PER_TILE_DIM) as u64),
+                    // sized by SdfResolution::max_tiles
+                    min_binding_size: Some(u32::min_size()),
+                },
+                count: None,
+            },
+            // indices
+            BindGroupLayoutEntry {
+                binding: 10,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(u32::min_size()),
+                },
+                count: None,
+            },
+            // sign bits
+            BindGroupLayoutEntry {
+                binding: 11,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    // sized by the resolution, see sign_word_count
+                    min_binding_size: Some(u32::min_size()),
+                },
+                count: None,
+            },
+            // interior tex
+            BindGroupLayoutEntry {
+                binding: 12,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::StorageTexture {
+                    access: StorageTextureAccess::ReadWrite,
+                    format: TextureFormat::R32Uint,
+                    view_dimension: TextureViewDimension::D3,
                 },
                 count: None,
             },
@@ -632,9 +1710,16 @@ impl FromWorld for SceneSdfPipeline {
                 BindGroupLayoutEntry {
                     binding: 0,
                     visibility: ShaderStages::COMPUTE,
-                    ty: BindingType::Buffer { ty: BufferBindingType::Storage { read_only: false }, has_dynamic_offset: false, min_binding_size: NonZeroU64::new(std::mem::size_of::<DispatchIndirect>() as u64) },
+                    ty: BindingType::Buffer { ty: BufferBindingType::Storage { read_only: false }, has_dynamic_offset: false, min_binding_size: NonZeroU64::new(std::mem::size_of::<DispatchIndirect>() as u64) },
+                    count: None,
+                },            
+                // coarse bins
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer { ty: BufferBindingType::Storage { read_only: false }, has_dynamic_offset: false, min_binding_size: Some(u32::min_size()) },
                     count: None,
-                },            
+                },
             ] }
         );
 
@@ -654,13 +1739,52 @@ impl FromWorld for SceneSdfPipeline {
 
         let output_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("sdf output layout"),
+            entries: &[
+                // output tex
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::ReadWrite,
+                        format: output_texture_format(output_format),
+                        view_dimension: TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+                // dynamic jfa dist tex
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::ReadWrite,
+                        format: nearest_format(resolution),
+                        view_dimension: TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+                // dynamic interior tex
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::ReadWrite,
+                        format: TextureFormat::R32Uint,
+                        view_dimension: TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let verify_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("sdf verify layout"),
             entries: &[BindGroupLayoutEntry {
                 binding: 0,
                 visibility: ShaderStages::COMPUTE,
-                ty: BindingType::StorageTexture {
-                    access: StorageTextureAccess::ReadWrite,
-                    format: TextureFormat::R32Float,
-                    view_dimension: TextureViewDimension::D3,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(VERIFY_RESULTS_SIZE),
                 },
                 count: None,
             }],
@@ -673,44 +1797,75 @@ impl FromWorld for SceneSdfPipeline {
         shaders.insert(asset_server.load("shader/bind.wgsl"));
         shaders.insert(asset_server.load("shader/addressing.wgsl"));
         shaders.insert(asset_server.load("shader/intersect.wgsl"));
+        shaders.insert(asset_server.load("shader/parity.wgsl"));
+        shaders.insert(asset_server.load("shader/encoding.wgsl"));
+        shaders.insert(asset_server.load("shader/output_bind.wgsl"));
+        shaders.insert(asset_server.load("shader/sample.wgsl"));
 
+        let transform_shader = world.resource::<AssetServer>().load("shader/transform.wgsl");
         let coarse_shader = world.resource::<AssetServer>().load("shader/coarse.wgsl");
         let fine_shader = world.resource::<AssetServer>().load("shader/fine.wgsl");
         let fine_blend_shader = world.resource::<AssetServer>().load("shader/fine_blend.wgsl");
+        let sign_shader = world.resource::<AssetServer>().load("shader/sign.wgsl");
         let jfa_shader = world.resource::<AssetServer>().load("shader/jfa simple.wgsl");
         let stitch_shader = world.resource::<AssetServer>().load("shader/jfa stitch.wgsl");
+        let verify_shader = world.resource::<AssetServer>().load("shader/verify.wgsl");
+        let refine_shader = world.resource::<AssetServer>().load("shader/refine.wgsl");
         let output_shader = world.resource::<AssetServer>().load("shader/output.wgsl");
 
         let mut pipeline_cache = world.resource_mut::<PipelineCache>();
-        let coarse_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+        let transform_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: None,
+            layout: Some(vec![working_layout.clone()]),
+            shader: transform_shader,
+            shader_defs: shader_defs.clone(),
+            entry_point: Cow::from("transform_tris"),
+        });
+
+        let mut coarse_pipeline = |entry_point: &'static str| pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             label: None,
             layout: Some(vec![working_layout.clone(), dispatch_layout.clone()]),
-            shader: coarse_shader,
-            shader_defs: vec![],
-            entry_point: Cow::from("coarse_raster"),
+            shader: coarse_shader.clone(),
+            shader_defs: shader_defs.clone(),
+            entry_point: Cow::from(entry_point),
         });
+        let coarse_clear_pipeline = coarse_pipeline("coarse_clear");
+        let coarse_count_pipeline = coarse_pipeline("coarse_count");
+        let coarse_scan_pipeline = coarse_pipeline("coarse_scan");
+        let coarse_write_pipeline = coarse_pipeline("coarse_write");
 
         let fine_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             label: None,
             layout: Some(vec![working_layout.clone()]),
             shader: fine_shader,
-            shader_defs: vec![],
+            shader_defs: shader_defs.clone(),
             entry_point: Cow::from("fine_raster"),
         });
 
         let fine_blend_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             label: None,
-            layout: Some(vec![working_layout.clone()]),
+            layout: Some(vec![working_layout.clone(), dispatch_layout.clone()]),
             shader: fine_blend_shader,
-            shader_defs: vec![],
+            shader_defs: shader_defs.clone(),
             entry_point: Cow::from("fine_blend"),
         });
 
+        let mut sign_pipeline = |entry_point: &'static str| pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: None,
+            layout: Some(vec![working_layout.clone()]),
+            shader: sign_shader.clone(),
+            shader_defs: shader_defs.clone(),
+            entry_point: Cow::from(entry_point),
+        });
+        let sign_clear_pipeline = sign_pipeline("sign_clear");
+        let sign_scan_pipeline = sign_pipeline("sign_scan");
+        let sign_resolve_pipeline = sign_pipeline("sign_resolve");
+
         let jfa_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             label: None,
             layout: Some(vec![working_layout.clone(), jfa_layout.clone()]),
             shader: jfa_shader,
-            shader_defs: vec![],
+            shader_defs: shader_defs.clone(),
             entry_point: Cow::from("jfa"),
         });
 
@@ -718,7 +1873,7 @@ impl FromWorld for SceneSdfPipeline {
             label: None,
             layout: Some(vec![working_layout.clone(), jfa_layout.clone()]),
             shader: stitch_shader,
-            shader_defs: vec![],
+            shader_defs: shader_defs.clone(),
             entry_point: Cow::from("stitch"),
         });
 
@@ -726,22 +1881,50 @@ impl FromWorld for SceneSdfPipeline {
             label: None,
             layout: Some(vec![working_layout.clone(), output_layout.clone()]),
             shader: output_shader,
-            shader_defs: vec![],
+            shader_defs: shader_defs.clone(),
             entry_point: Cow::from("output"),
         });
 
+        let refine_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: None,
+            layout: Some(vec![working_layout.clone(), output_layout.clone(), dispatch_layout.clone()]),
+            shader: refine_shader,
+            shader_defs: shader_defs.clone(),
+            entry_point: Cow::from("refine"),
+        });
+
+        let verify_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: None,
+            layout: Some(vec![working_layout.clone(), output_layout.clone(), verify_layout.clone()]),
+            shader: verify_shader,
+            shader_defs: shader_defs.clone(),
+            entry_point: Cow::from("verify"),
+        });
+
         Self {
             shaders,
             working_layout,
             dispatch_layout,
-            coarse_pipeline,
+            transform_pipeline,
+            coarse_clear_pipeline,
+            coarse_count_pipeline,
+            coarse_scan_pipeline,
+            coarse_write_pipeline,
             fine_pipeline,
             fine_blend_pipeline,
+            sign_clear_pipeline,
+            sign_scan_pipeline,
+            sign_resolve_pipeline,
             jfa_layout,
             jfa_pipeline,
             stitch_pipeline,
             output_layout,
+            verify_layout,
             output_pipeline,
+            refine_pipeline,
+            verify_pipeline,
+            resolution: resolution.clone(),
+            output_format,
         }
     }
 }
@@ -785,37 +1968,51 @@ fn queue_sdf_data(
     mut count: Local<usize>,
     frame: Res<FrameCount>,
 ) {
-    let (index_offset_counts, transforms): (Vec<UVec4>, Vec<Mat4>) = sdf_meshes
-        .iter()
-        .enumerate()
-        .map(|(ix, scene_data)| {
-            let offset_and_count = pos_data.mesh_offsets[&scene_data.handle];
-            (
-                UVec4::new(
-                    ix as u32,
-                    offset_and_count.0 as u32,
-                    offset_and_count.1 as u32,
-                    0,
-                ),
-                scene_data.transform,
-            )
-        })
-        .unzip();
-    let vertex_count: u32 = index_offset_counts.iter().map(|v| v.z).sum();
-    // println!("vertex count: {}", vertex_count);
-    // println!("mesh count: {}", transforms.len());
-    // println!("tri count: {}", vertex_count / 3);
-
+    pos_data.verify_this_frame = false;
     pos_data.cascade_header_buffer.clear();
+    pos_data.static_updates.clear();
+    pos_data.dynamic_updates.clear();
+    pos_data.output_updates.clear();
+    let (signed, leak_tolerance) = state.sign_info();
     for (i, cascade) in state.cascades.iter().enumerate() {
-        if cascade.redraw != IVec3::ZERO {
-            pos_data.cascade_header_buffer.push(SdfCascadeInfo {
-                tile_size: cascade.settings.tile_size(),
-                origin: cascade.last_origin.unwrap_or_default().extend(0),
-                redraw: cascade.redraw.extend(0),
-                index: i as u32,
+        let full_region = (IVec3::ZERO, IVec3::splat(cascade.tile_dim_count));
+        let info = |redraw: IVec3, (update_min, update_max): (IVec3, IVec3)| SdfCascadeInfo {
+            tile_size: cascade.tile_size(),
+            origin: cascade.last_origin.unwrap_or_default().extend(0),
+            redraw: redraw.extend(0),
+            update_min: update_min.extend(0),
+            update_max: update_max.extend(0),
+            index: i as u32,
+            narrow_band: state.narrow_band,
+            signed,
+            leak_tolerance,
+            voxelization: state.voxelization as u32,
+            truncation: state.truncation(cascade),
+        };
+
+        if cascade.update_region.is_none() && !cascade.dynamic_redraw {
+            continue;
+        }
+
+        let output_offset = pos_data.cascade_header_buffer.push(info(cascade.redraw, full_region));
+        pos_data.output_updates.push(output_offset);
+
+        if let Some(region) = cascade.update_region {
+            let offset = pos_data.cascade_header_buffer.push(info(cascade.redraw, region));
+            pos_data.static_updates.push(StaticUpdate {
+                offset,
+                output_offset,
+                redraw: cascade.redraw,
+                stitch_count: state.stitch_region(cascade).map(|(min, max)| ((max - min).as_uvec3() + 7) / 8),
             });
         }
+
+        if cascade.dynamic_redraw {
+            // dynamic layer is always redrawn in full
+            let redraw = IVec3::new(cascade.tile_dim_count, 0, 0);
+            let offset = pos_data.cascade_header_buffer.push(info(redraw, full_region));
+            pos_data.dynamic_updates.push((offset, redraw));
+        }
     }
 
     if frame.0 == 10000 {
@@ -904,56 +2101,20 @@ fn queue_sdf_data(
     pos_data.record_stats = record_stats;
 
     // initialize jfa buffers - todo should be in prepare instead of queue
-    if pos_data.jfa_buffers.as_ref().map(|jfa| jfa.4 ) != Some(state.cascades.len()) {
-        let vox_per_dim = VOXELS_PER_TILE_DIM * TILE_DIM_COUNT;
-
-        let seed_texture_desc = TextureDescriptor {
-            label: Some("sdf output texture"),
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: bevy::render::render_resource::TextureDimension::D3,
-            format: TextureFormat::Rg32Uint,
-            size: Extent3d {
-                width: vox_per_dim as u32 * state.cascades.len() as u32,
-                height: vox_per_dim as u32,
-                depth_or_array_layers: vox_per_dim as u32,
-            },
-            usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
-        };
-
-        let seed_texture = device.create_texture(&seed_texture_desc);
-        let seed_view = seed_texture.create_view(&TextureViewDescriptor::default());
-
-        let nearest_texture_desc = TextureDescriptor {
-            label: Some("sdf output texture"),
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: bevy::render::render_resource::TextureDimension::D3,
-            format: TextureFormat::Rgba8Sint,
-            size: Extent3d {
-                width: vox_per_dim as u32 * state.cascades.len() as u32,
-                height: vox_per_dim as u32,
-                depth_or_array_layers: vox_per_dim as u32,
-            },
-            usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
-        };
-
-        let nearest_texture = device.create_texture(&nearest_texture_desc);
-        let nearest_view = nearest_texture.create_view(&TextureViewDescriptor::default());
-
-        pos_data.jfa_buffers = Some((
-            seed_texture,
-            seed_view,
-            nearest_texture,
-            nearest_view,
-            state.cascades.len(),
-        ));
-
-        for sz in [64, 32, 16, 8, 4, 2, 1] {
+    if pos_data.jfa_buffers.as_ref().map(|jfa| jfa.cascade_count) != Some(state.cascades.len()) {
+        pos_data.jfa_buffers = Some(create_jfa_textures(&device, &state.resolution, state.cascades.len()));
+        pos_data.dynamic_jfa_buffers = Some(create_jfa_textures(&device, &state.resolution, state.cascades.len()));
+
+        // halving jumps from half the cascade size
+        pos_data.jfa_params.clear();
+        pos_data.jfa_param_offsets.clear();
+        let mut sz = state.resolution.voxels_per_dim() as i32 / 2;
+        while sz > 0 {
             let offset = pos_data.jfa_params.push(JfaParams {
                 jump_size: sz,
             });
             pos_data.jfa_param_offsets.push(offset);
+            sz /= 2;
         }
         pos_data.jfa_params.write_buffer(&device, &queue);
         pos_data.jfa_bindgroup = Some(device.create_bind_group(&BindGroupDescriptor {
@@ -976,11 +2137,48 @@ fn queue_sdf_data(
         }));
     }
 
+    if pos_data.sign_bits.is_none() {
+        pos_data.sign_bits = Some(device.create_buffer(&BufferDescriptor{
+            label: Some("sign bits buffer"),
+            size: (sign_word_count(&state.resolution) as usize * std::mem::size_of::<u32>()) as u64,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        }));
+    }
+
+    // coarse output, (tile, id offset) per virtual tile and the tile ids
+    if pos_data.coarse_counts_per_tile.is_none() {
+        pos_data.coarse_counts_per_tile = Some(device.create_buffer(&BufferDescriptor{
+            label: Some("coarse counts per tile buffer"),
+            size: UVec2::min_size().get() * state.resolution.max_tiles as u64,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        }));
+    }
+    if pos_data.coarse_tile_ids.is_none() {
+        pos_data.coarse_tile_ids = Some(device.create_buffer(&BufferDescriptor{
+            label: Some("coarse tile ids buffer"),
+            size: u32::min_size().get() * state.resolution.max_id_count as u64,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        }));
+    }
+
+    // coarse histogram, write indices and offsets
+    if pos_data.coarse_bins.is_none() {
+        pos_data.coarse_bins = Some(device.create_buffer(&BufferDescriptor{
+            label: Some("coarse bins buffer"),
+            size: coarse_bins_size(&state.resolution),
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        }));
+    }
+
     // fine output buffer
     if pos_data.fine_tile_output.is_none() {
         pos_data.fine_tile_output = Some(device.create_buffer(&BufferDescriptor{
             label: Some("fine output buffer"),
-            size: (8 * VOXELS_PER_TILE_DIM * VOXELS_PER_TILE_DIM * VOXELS_PER_TILE_DIM * MAX_TILES) as u64,
+            size: 4 * seed_words(&state.resolution) as u64 * state.resolution.voxels_per_tile() as u64 * state.resolution.max_tiles as u64,
             usage: BufferUsages::STORAGE,
             mapped_at_creation: false,
         }));
@@ -988,140 +2186,407 @@ fn queue_sdf_data(
 
     // init output buffers
     if output_data.output_buffer.is_none() || output_data.output_buffer.as_ref().unwrap().2 != state.cascades.len() {
-        let vox_per_dim = VOXELS_PER_TILE_DIM * TILE_DIM_COUNT;
-        let width = (vox_per_dim * state.cascades.len()) as u32;
+        let vox_per_dim = state.resolution.voxels_per_dim();
+        let width = vox_per_dim * state.cascades.len() as u32;
     
         let texture_desc = TextureDescriptor {
             label: Some("sdf output texture"),
             mip_level_count: 1,
             sample_count: 1,
             dimension: bevy::render::render_resource::TextureDimension::D3,
-            format: TextureFormat::R32Float,
+            format: output_texture_format(state.output_format),
             size: Extent3d {
                 width,
-                height: vox_per_dim as u32,
-                depth_or_array_layers: vox_per_dim as u32,
+                height: vox_per_dim,
+                depth_or_array_layers: vox_per_dim,
             },
-            usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+            usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
         };
 
         let texture = device.create_texture(&texture_desc);
         let view = texture.create_view(&TextureViewDescriptor::default());
 
-        pos_data
-            .output_bindgroups
-            .push(device.create_bind_group(&BindGroupDescriptor {
-                label: Some("output bindgroup"),
-                layout: &pipeline.output_layout,
+        output_data.output_buffer = Some((texture, view, state.cascades.len()));
+    }
+
+    pos_data.output_bindgroup = Some(device.create_bind_group(&BindGroupDescriptor {
+        label: Some("output bindgroup"),
+        layout: &pipeline.output_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&output_data.output_buffer.as_ref().unwrap().1),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(&pos_data.dynamic_jfa_buffers.as_ref().unwrap().nearest_view),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::TextureView(&pos_data.dynamic_jfa_buffers.as_ref().unwrap().interior_view),
+            },
+        ],
+    }));
+
+    // brute force verification, with one set of results in flight at a time
+    if !state.verify {
+        pos_data.verify = None;
+    } else {
+        if pos_data.verify.is_none() {
+            let results = device.create_buffer(&BufferDescriptor {
+                label: Some("sdf verify results"),
+                size: VERIFY_RESULTS_SIZE,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let readback = device.create_buffer(&BufferDescriptor {
+                label: Some("sdf verify readback"),
+                size: VERIFY_RESULTS_SIZE,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bindgroup = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("verify bindgroup"),
+                layout: &pipeline.verify_layout,
                 entries: &[BindGroupEntry {
                     binding: 0,
-                    resource: BindingResource::TextureView(&view),
+                    resource: results.as_entire_binding(),
                 }],
-            }));
-        output_data.output_buffer = Some((texture, view, state.cascades.len()));
+            });
+            pos_data.verify = Some(SdfVerifyData {
+                results,
+                readback,
+                bindgroup,
+                state: ReadbackState::Idle,
+                mapped: Default::default(),
+                map_failed: Default::default(),
+            });
+        }
+
+        let verify = pos_data.verify.as_mut().unwrap();
+        let mut verify_this_frame = false;
+        match verify.state {
+            ReadbackState::Idle => {
+                queue.write_buffer(&verify.results, 0, &[0; VERIFY_RESULTS_SIZE as usize]);
+                verify.state = ReadbackState::Copied;
+                verify_this_frame = true;
+            }
+            ReadbackState::Copied => {
+                let (mapped, map_failed) = (verify.mapped.clone(), verify.map_failed.clone());
+                device.map_buffer(&verify.readback.slice(..), wgpu::MapMode::Read, move |res| {
+                    match res {
+                        Ok(_) => mapped.store(true, Ordering::Release),
+                        Err(e) => {
+                            warn!("sdf verify: failed to map results: {e}");
+                            map_failed.store(true, Ordering::Release);
+                        }
+                    }
+                });
+                verify.state = ReadbackState::Mapping;
+            }
+            ReadbackState::Mapping => {
+                if verify.map_failed.swap(false, Ordering::Acquire) {
+                    // skip these results
+                    verify.state = ReadbackState::Idle;
+                } else if verify.mapped.swap(false, Ordering::Acquire) {
+                    let range = verify.readback.slice(..).get_mapped_range();
+                    let results: Vec<u32> = range.chunks(4).map(|chunk| {
+                        let mut bytes = [0u8; 4];
+                        bytes.clone_from_slice(chunk);
+                        u32::from_le_bytes(bytes)
+                    }).collect();
+                    drop(range);
+                    verify.readback.unmap();
+                    verify.state = ReadbackState::Idle;
+
+                    let (max_error, failures, samples) = (f32::from_bits(results[0]), results[1], results[2]);
+                    // no samples if the pipeline wasn't ready
+                    if failures > 0 {
+                        warn!("sdf verify: {samples} samples, max overestimate {max_error:.3} voxels, {failures} over half a voxel");
+                    } else if samples > 0 {
+                        info!("sdf verify: {samples} samples, max overestimate {max_error:.3} voxels");
+                    }
+                }
+            }
+        }
+        pos_data.verify_this_frame = verify_this_frame;
     }
 
-    pos_data.mesh_header_buffer.set(SdfMeshHeader {
-        mesh_count: index_offset_counts.len() as u32,
-        tri_count: vertex_count / 3,
-        index_offset_count: index_offset_counts,
-    });
+    let (mesh_header, transforms) = mesh_data(sdf_meshes.iter().filter(|data| !data.dynamic), &pos_data);
+    pos_data.static_tri_count = mesh_header.tri_count;
+    pos_data.mesh_header_buffer.set(mesh_header);
     pos_data.mesh_header_buffer.write_buffer(&device, &queue);
-
-    pos_data
-        .transforms_buffer
-        .set(SdfTransforms { t: transforms });
+    pos_data.transforms_buffer.set(transforms);
     pos_data.transforms_buffer.write_buffer(&device, &queue);
 
-    pos_data.working_bindgroup = Some(device.create_bind_group(&BindGroupDescriptor {
+    let (mesh_header, transforms) = mesh_data(sdf_meshes.iter().filter(|data| data.dynamic), &pos_data);
+    pos_data.dynamic_tri_count = mesh_header.tri_count;
+    pos_data.dynamic_mesh_header_buffer.set(mesh_header);
+    pos_data.dynamic_mesh_header_buffer.write_buffer(&device, &queue);
+    pos_data.dynamic_transforms_buffer.set(transforms);
+    pos_data.dynamic_transforms_buffer.write_buffer(&device, &queue);
+
+    // the static and dynamic updates run one after the other so share a buffer sized for the larger
+    let tri_count = pos_data.static_tri_count.max(pos_data.dynamic_tri_count).max(1) as usize;
+    if pos_data.transformed_tris.as_ref().map_or(true, |(_, capacity)| *capacity < tri_count) {
+        let capacity = tri_count.next_power_of_two();
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("sdf transformed triangles"),
+            size: TransformedTriData::min_size().get() * capacity as u64,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        pos_data.transformed_tris = Some((buffer, capacity));
+    }
+
+    pos_data.working_bindgroup = Some(working_bindgroup(&device, &pipeline.working_layout, &pos_data, false));
+    pos_data.dynamic_working_bindgroup = Some(working_bindgroup(&device, &pipeline.working_layout, &pos_data, true));
+
+    pos_data.dispatch_bindgroup = Some(device.create_bind_group(&BindGroupDescriptor {
+        label: Some("sdf dispatch bindgroup"),
+        layout: &pipeline.dispatch_layout,
+        entries: &[
+            // dispatch 
+            BindGroupEntry {
+                binding: 0,
+                resource: pos_data.dispatch_fine_tiles.as_ref().unwrap().as_entire_binding(),
+            },
+            // coarse bins
+            BindGroupEntry {
+                binding: 1,
+                resource: pos_data.coarse_bins.as_ref().unwrap().as_entire_binding(),
+            },
+        ],
+    }));
+        
+    let (signed, leak_tolerance) = state.sign_info();
+    let headers = state.cascades.iter().enumerate().map(|(index, cascade)| SdfCascadeInfo {
+        origin: cascade.last_origin.unwrap_or_default().extend(0),
+        tile_size: cascade.tile_size(),
+        redraw: IVec4::ZERO,
+        update_min: IVec4::ZERO,
+        update_max: IVec4::ZERO,
+        index: index as u32,
+        narrow_band: state.narrow_band,
+        signed,
+        leak_tolerance,
+        voxelization: state.voxelization as u32,
+        truncation: state.truncation(cascade),
+    });
+
+    output_data.sdf_header_buffer.set(SdfCascadeInfos {
+        num_cascades: state.cascades.len() as u32,
+        cascades: headers.collect(),
+    });
+    output_data.sdf_header_buffer.write_buffer(&device, &queue);
+}
+
+struct JfaTextures {
+    _seed: Texture,
+    seed_view: TextureView,
+    _nearest: Texture,
+    nearest_view: TextureView,
+    // interior bits for signed distances, see parity.wgsl
+    _interior: Texture,
+    interior_view: TextureView,
+    cascade_count: usize,
+}
+
+fn create_jfa_textures(device: &RenderDevice, resolution: &SdfResolution, cascade_count: usize) -> JfaTextures {
+    let vox_per_dim = resolution.voxels_per_dim() as usize;
+
+    let seed_texture_desc = TextureDescriptor {
+        label: Some("sdf output texture"),
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: bevy::render::render_resource::TextureDimension::D3,
+        format: seed_format(resolution),
+        size: Extent3d {
+            width: vox_per_dim as u32 * cascade_count as u32,
+            height: vox_per_dim as u32,
+            depth_or_array_layers: vox_per_dim as u32,
+        },
+        usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+    };
+
+    let seed_texture = device.create_texture(&seed_texture_desc);
+    let seed_view = seed_texture.create_view(&TextureViewDescriptor::default());
+
+    let nearest_texture_desc = TextureDescriptor {
+        label: Some("sdf output texture"),
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: bevy::render::render_resource::TextureDimension::D3,
+        format: nearest_format(resolution),
+        size: Extent3d {
+            width: vox_per_dim as u32 * cascade_count as u32,
+            height: vox_per_dim as u32,
+            depth_or_array_layers: vox_per_dim as u32,
+        },
+        usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+    };
+
+    let nearest_texture = device.create_texture(&nearest_texture_desc);
+    let nearest_view = nearest_texture.create_view(&TextureViewDescriptor::default());
+
+    // a bit per voxel, packed along x
+    let interior_texture_desc = TextureDescriptor {
+        label: Some("sdf interior texture"),
+        format: TextureFormat::R32Uint,
+        size: Extent3d {
+            width: (vox_per_dim / 32) as u32 * cascade_count as u32,
+            ..nearest_texture_desc.size
+        },
+        ..nearest_texture_desc
+    };
+
+    let interior_texture = device.create_texture(&interior_texture_desc);
+    let interior_view = interior_texture.create_view(&TextureViewDescriptor::default());
+
+    JfaTextures {
+        _seed: seed_texture,
+        seed_view,
+        _nearest: nearest_texture,
+        nearest_view,
+        _interior: interior_texture,
+        interior_view,
+        cascade_count,
+    }
+}
+
+fn mesh_data<'a>(
+    sdf_meshes: impl Iterator<Item = &'a SdfSceneData>,
+    pos_data: &SdfWorkingData,
+) -> (SdfMeshHeader, SdfTransforms) {
+    let mut tri_offset = 0;
+    let (instances, transforms): (Vec<SdfMeshInstance>, Vec<Mat4>) = sdf_meshes
+        .filter_map(|scene_data| {
+            let (index_offset, index_count) = pos_data.indices.get(&scene_data.handle)?;
+            let (vertex_offset, _) = pos_data.vertices.get(&scene_data.handle)?;
+            let (vertex_format, dequantize) = pos_data.mesh_formats.get(&scene_data.handle)?;
+            Some((index_offset, index_count, vertex_offset, *vertex_format, scene_data.transform * *dequantize, scene_data.half_thickness))
+        })
+        .enumerate()
+        .map(|(ix, (index_offset, index_count, vertex_offset, vertex_format, transform, half_thickness))| {
+            let instance = SdfMeshInstance {
+                transform_index: ix as u32,
+                index_offset: index_offset as u32,
+                index_count: index_count as u32,
+                vertex_offset: vertex_offset as u32,
+                vertex_format: vertex_format as u32,
+                tri_offset,
+                half_thickness,
+            };
+            tri_offset += index_count as u32 / 3;
+            (instance, transform)
+        })
+        .unzip();
+    // println!("mesh count: {}", transforms.len());
+    // println!("tri count: {}", tri_offset);
+
+    (
+        SdfMeshHeader {
+            mesh_count: instances.len() as u32,
+            tri_count: tri_offset,
+            instances,
+        },
+        SdfTransforms { t: transforms },
+    )
+}
+
+// the static and dynamic layers share everything except their mesh lists and jfa textures
+fn working_bindgroup(device: &RenderDevice, layout: &BindGroupLayout, pos_data: &SdfWorkingData, dynamic: bool) -> BindGroup {
+    let (mesh_header_buffer, transforms_buffer, jfa_buffers) = match dynamic {
+        false => (&pos_data.mesh_header_buffer, &pos_data.transforms_buffer, pos_data.jfa_buffers.as_ref().unwrap()),
+        true => (&pos_data.dynamic_mesh_header_buffer, &pos_data.dynamic_transforms_buffer, pos_data.dynamic_jfa_buffers.as_ref().unwrap()),
+    };
+
+    device.create_bind_group(&BindGroupDescriptor {
         label: Some("sdf coarse bindgroup"),
-        layout: &pipeline.working_layout,
+        layout,
         entries: &[
             // mesh header
             BindGroupEntry {
                 binding: 0,
-                resource: pos_data.mesh_header_buffer.binding().unwrap(),
+                resource: mesh_header_buffer.binding().unwrap(),
             },
             // transforms
             BindGroupEntry {
                 binding: 1,
-                resource: pos_data.transforms_buffer.binding().unwrap(),
+                resource: transforms_buffer.binding().unwrap(),
             },
-            // triangles
+            // vertices
             BindGroupEntry {
                 binding: 2,
-                resource: pos_data.tris_buffer.binding().unwrap(),
+                resource: pos_data.vertices.binding().unwrap(),
             },
             // cascade(s)
             BindGroupEntry {
                 binding: 3,
                 resource: pos_data.cascade_header_buffer.binding().unwrap(),
             },
-            // triangle data out
+            // transformed triangles
             BindGroupEntry {
                 binding: 4,
-                resource: pos_data.coarse_tri_buffer.binding().unwrap(),
+                resource: pos_data.transformed_tris.as_ref().unwrap().0.as_entire_binding(),
             },
             // counts out
             BindGroupEntry {
                 binding: 5,
-                resource: pos_data.coarse_counts_per_tile.binding().unwrap(),
+                resource: pos_data.coarse_counts_per_tile.as_ref().unwrap().as_entire_binding(),
             },
             // ids out
             BindGroupEntry {
                 binding: 6,
-                resource: pos_data.coarse_tile_ids.binding().unwrap(),
+                resource: pos_data.coarse_tile_ids.as_ref().unwrap().as_entire_binding(),
             },
             // jfa seed tex
             BindGroupEntry {
                 binding: 7,
-                resource: BindingResource::TextureView(&pos_data.jfa_buffers.as_ref().unwrap().1),
+                resource: BindingResource::TextureView(&jfa_buffers.seed_view),
             },
             // jfa dist tex
             BindGroupEntry {
                 binding: 8,
-                resource: BindingResource::TextureView(&pos_data.jfa_buffers.as_ref().unwrap().3),
+                resource: BindingResource::TextureView(&jfa_buffers.nearest_view),
             },
             // fine output tex
             BindGroupEntry {
                 binding: 9,
                 resource: pos_data.fine_tile_output.as_ref().unwrap().as_entire_binding(),
-            }
-        ],
-    }));
-
-    pos_data.dispatch_bindgroup = Some(device.create_bind_group(&BindGroupDescriptor {
-        label: Some("sdf dispatch bindgroup"),
-        layout: &pipeline.dispatch_layout,
-        entries: &[
-            // dispatch 
+            },
+            // indices
             BindGroupEntry {
-                binding: 0,
-                resource: pos_data.dispatch_fine_tiles.as_ref().unwrap().as_entire_binding(),
-            }
+                binding: 10,
+                resource: pos_data.indices.binding().unwrap(),
+            },
+            // sign bits
+            BindGroupEntry {
+                binding: 11,
+                resource: pos_data.sign_bits.as_ref().unwrap().as_entire_binding(),
+            },
+            // interior tex
+            BindGroupEntry {
+                binding: 12,
+                resource: BindingResource::TextureView(&jfa_buffers.interior_view),
+            },
         ],
-    }));
-        
-    let headers = state.cascades.iter().enumerate().map(|(index, cascade)| SdfCascadeInfo {
-        origin: cascade.last_origin.unwrap_or_default().extend(0),
-        tile_size: cascade.settings.tile_size(),
-        redraw: IVec4::ZERO,
-        index: index as u32,
-    });
-
-    output_data.sdf_header_buffer.set(SdfCascadeInfos {
-        num_cascades: state.cascades.len() as u32,
-        cascades: headers.collect(),
-    });
-    output_data.sdf_header_buffer.write_buffer(&device, &queue);
+    })
 }
 
 #[derive(ShaderType)]
 pub struct SdfCascadeInfo {
     origin: IVec4,
     redraw: IVec4,
+    update_min: IVec4,
+    update_max: IVec4,
     tile_size: f32,
     index: u32,
+    narrow_band: u32,
+    signed: u32,
+    leak_tolerance: u32,
+    voxelization: u32,
+    truncation: u32,
 }
 
 #[derive(ShaderType, Default)]
@@ -1131,12 +2596,26 @@ pub struct SdfCascadeInfos {
     cascades: Vec<SdfCascadeInfo>,
 }
 
+#[derive(ShaderType, Default, Debug)]
+struct SdfMeshInstance {
+    transform_index: u32,
+    // offsets into the shared index and vertex buffers
+    index_offset: u32,
+    index_count: u32,
+    vertex_offset: u32,
+    vertex_format: u32,
+    // first triangle of this instance in the update's triangle list
+    tri_offset: u32,
+    // SdfMinThickness / 2
+    half_thickness: f32,
+}
+
 #[derive(ShaderType, Default, Debug)]
 struct SdfMeshHeader {
     mesh_count: u32,
     tri_count: u32,
     #[size(runtime)]
-    index_offset_count: Vec<UVec4>,
+    instances: Vec<SdfMeshInstance>,
 }
 
 #[derive(ShaderType, Default)]
@@ -1158,13 +2637,20 @@ impl Node for SceneSdfNode {
         if pos_data.working_bindgroup.is_none() {
             return Ok(());
         }
-
-        let state = world.resource::<SdfState>();
+        let device_limits = world.resource::<RenderDevice>().limits();
 
         let pipeline_cache = world.resource::<PipelineCache>();
         let pipeline = world.resource::<SceneSdfPipeline>();
 
-        let Some(coarse_pipeline) = pipeline_cache.get_compute_pipeline(pipeline.coarse_pipeline) else {
+        let Some(transform_pipeline) = pipeline_cache.get_compute_pipeline(pipeline.transform_pipeline) else {
+            return Ok(());
+        };
+        let (Some(coarse_clear_pipeline), Some(coarse_count_pipeline), Some(coarse_scan_pipeline), Some(coarse_write_pipeline)) = (
+            pipeline_cache.get_compute_pipeline(pipeline.coarse_clear_pipeline),
+            pipeline_cache.get_compute_pipeline(pipeline.coarse_count_pipeline),
+            pipeline_cache.get_compute_pipeline(pipeline.coarse_scan_pipeline),
+            pipeline_cache.get_compute_pipeline(pipeline.coarse_write_pipeline),
+        ) else {
             return Ok(());
         };
         let fine_pipeline = match pipeline_cache.get_compute_pipeline(pipeline.fine_pipeline) {
@@ -1214,54 +2700,172 @@ impl Node for SceneSdfNode {
             }
         };
 
-        for cascade in state.cascades.iter() {
-            if cascade.redraw != IVec3::ZERO {
-                stamp(&mut pass, StampIndex::START);
-
-                // coarse
-                pass.set_pipeline(coarse_pipeline);
-                pass.set_bind_group(0, pos_data.working_bindgroup.as_ref().unwrap(), &[0]);
-                pass.set_bind_group(1, pos_data.dispatch_bindgroup.as_ref().unwrap(), &[]);
-                pass.dispatch_workgroups(1, 1, 1);
-                stamp(&mut pass, StampIndex::COARSE);
-
-                // fine
-                pass.set_pipeline(fine_pipeline);
-                pass.dispatch_workgroups_indirect(pos_data.dispatch_fine_tiles.as_ref().unwrap(), 0);
-                stamp(&mut pass, StampIndex::FINE);
-
-                // fine blend
-                pass.set_pipeline(fine_blend_pipeline);
-                pass.dispatch_workgroups(1, 1, 1);
-                stamp(&mut pass, StampIndex::FINEBLEND);
-
-                // jfa
-                pass.set_pipeline(jfa_pipeline);
-                let count = ((cascade.redraw.abs() + TILE_DIM_COUNT as i32 - 1) % TILE_DIM_COUNT as i32 + 1).as_uvec3();
-                for offset in &pos_data.jfa_param_offsets {
-                    pass.set_bind_group(1, pos_data.jfa_bindgroup.as_ref().unwrap(), &[*offset]);
-                    pass.dispatch_workgroups(count.x, count.y, count.z);
+        let state = world.resource::<SdfState>();
+        let resolution = &state.resolution;
+        // pipelines and buffers are switching to a new resolution
+        if pos_data.resolution.as_ref() != Some(resolution) {
+            return Ok(());
+        }
+        let tile_dim_count = resolution.tile_dim_count as i32;
+        let tile_count = resolution.tile_count();
+        let voxels_per_dim = resolution.voxels_per_dim();
+        let count = (voxels_per_dim as f32 / 8.0).ceil() as u32;
+        let narrow_band = state.narrow_band;
+        let sign_pipelines = match state.sign {
+            SdfSign::Unsigned => None,
+            SdfSign::Signed { .. } => {
+                let (Some(clear), Some(scan), Some(resolve)) = (
+                    pipeline_cache.get_compute_pipeline(pipeline.sign_clear_pipeline),
+                    pipeline_cache.get_compute_pipeline(pipeline.sign_scan_pipeline),
+                    pipeline_cache.get_compute_pipeline(pipeline.sign_resolve_pipeline),
+                ) else {
+                    return Ok(());
+                };
+                Some((clear, scan, resolve))
+            }
+        };
+
+        // dynamic layer updates, then static layer updates, so a static update can output its cascade with this
+        // frame's dynamic layer and refine the result while its coarse lists are live.
+        // we only record timings for the static layer
+        let dynamic_updates = pos_data.dynamic_updates.iter().map(|(offset, redraw)| (true, offset, redraw, None, None));
+        let static_updates = pos_data.static_updates.iter().map(|update| (false, &update.offset, &update.redraw, update.stitch_count, Some(update.output_offset)));
+        for (dynamic, offset, redraw, stitch_count, output_offset) in dynamic_updates.chain(static_updates) {
+            let (working_bindgroup, tri_count, timed) = match dynamic {
+                false => (pos_data.working_bindgroup.as_ref().unwrap(), pos_data.static_tri_count, true),
+                true => (pos_data.dynamic_working_bindgroup.as_ref().unwrap(), pos_data.dynamic_tri_count, false),
+            };
+            let stamp = |pass: &mut ComputePass, ix: StampIndex| {
+                if timed {
+                    stamp(pass, ix);
                 }
-                stamp(&mut pass, StampIndex::JFA);
+            };
 
-                let count = ((VOXELS_PER_TILE_DIM * TILE_DIM_COUNT) as f32 / 8.0).ceil() as u32;
+            stamp(&mut pass, StampIndex::START);
+
+            // per-triangle passes, 64 threads per workgroup
+            let workgroups = (tri_count + 63) / 64;
+            let max_workgroups = device_limits.max_compute_workgroups_per_dimension;
+            let tri_dispatch = UVec2::new(workgroups.min(max_workgroups), (workgroups + max_workgroups - 1) / max_workgroups);
+
+            // transform (timed with coarse)
+            pass.set_pipeline(transform_pipeline);
+            pass.set_bind_group(0, working_bindgroup, &[*offset]);
+            pass.dispatch_workgroups(tri_dispatch.x, tri_dispatch.y, 1);
+
+            // coarse
+            pass.set_bind_group(1, pos_data.dispatch_bindgroup.as_ref().unwrap(), &[]);
+            pass.set_pipeline(coarse_clear_pipeline);
+            pass.dispatch_workgroups((tile_count + 255) / 256, 1, 1);
+            pass.set_pipeline(coarse_count_pipeline);
+            pass.dispatch_workgroups(tri_dispatch.x, tri_dispatch.y, 1);
+            pass.set_pipeline(coarse_scan_pipeline);
+            pass.dispatch_workgroups(1, 1, 1);
+            pass.set_pipeline(coarse_write_pipeline);
+            pass.dispatch_workgroups(tri_dispatch.x, tri_dispatch.y, 1);
+            stamp(&mut pass, StampIndex::COARSE);
+
+            // crossings are accumulated by the fine raster
+            if let Some((sign_clear_pipeline, ..)) = sign_pipelines {
+                pass.set_pipeline(sign_clear_pipeline);
+                pass.dispatch_workgroups((sign_word_count(resolution) + 255) / 256, 1, 1);
+            }
 
-                // stitch
+            // fine
+            pass.set_pipeline(fine_pipeline);
+            pass.dispatch_workgroups_indirect(pos_data.dispatch_fine_tiles.as_ref().unwrap(), 0);
+            stamp(&mut pass, StampIndex::FINE);
+
+            // fine blend, a workgroup per real tile (group 1 is still the dispatch bindgroup)
+            pass.set_pipeline(fine_blend_pipeline);
+            pass.dispatch_workgroups(tile_count, 1, 1);
+
+            // crossings -> interior (timed with fine blend)
+            if let Some((_, sign_scan_pipeline, sign_resolve_pipeline)) = sign_pipelines {
+                pass.set_pipeline(sign_scan_pipeline);
+                pass.dispatch_workgroups(voxels_per_dim * voxels_per_dim / 64, 1, 1);
+                // a thread per packed interior word
+                pass.set_pipeline(sign_resolve_pipeline);
+                pass.dispatch_workgroups((voxels_per_dim / 32 + 3) / 4, count, count);
+            }
+            stamp(&mut pass, StampIndex::FINEBLEND);
+
+            // jfa
+            pass.set_pipeline(jfa_pipeline);
+            // 8^3 voxels per workgroup over the redrawn tiles
+            let jfa_tiles = ((redraw.abs() + tile_dim_count - 1) % tile_dim_count + 1).as_uvec3();
+            let jfa_count = (jfa_tiles * resolution.voxels_per_tile_dim + 7) / 8;
+            for jfa_offset in &pos_data.jfa_param_offsets {
+                pass.set_bind_group(1, pos_data.jfa_bindgroup.as_ref().unwrap(), &[*jfa_offset]);
+                pass.dispatch_workgroups(jfa_count.x, jfa_count.y, jfa_count.z);
+            }
+            stamp(&mut pass, StampIndex::JFA);
+
+            // stitch
+            // the dynamic layer is always redrawn in full so doesn't need stitching,
+            // and in-place rebuilds are flooded over the whole cascade.
+            // each jump size is a pass over the stitch region. for unclamped cascades that's the whole cascade, so a
+            // scroll costs about as much as the jump passes of a full redraw. truncated cascades only visit the slab
+            // and the band beside it
+            if let Some(stitch_count) = stitch_count {
                 pass.set_pipeline(stitch_pipeline);
-                pass.dispatch_workgroups(count, count, count);
+                for jfa_offset in &pos_data.jfa_param_offsets {
+                    pass.set_bind_group(1, pos_data.jfa_bindgroup.as_ref().unwrap(), &[*jfa_offset]);
+                    pass.dispatch_workgroups(stitch_count.x, stitch_count.y, stitch_count.z);
+                }
                 stamp(&mut pass, StampIndex::STITCH);
+            }
+
+            let Some(output_offset) = output_offset else {
+                continue;
+            };
 
-                // output
-                pass.set_pipeline(output_pipeline);
-                pass.set_bind_group(1, pos_data.output_bindgroups.get(0).as_ref().unwrap(), &[]);
-                pass.dispatch_workgroups(count, count, count);
-                stamp(&mut pass, StampIndex::OUTPUT);
+            // output, merging static and dynamic
+            pass.set_pipeline(output_pipeline);
+            pass.set_bind_group(0, working_bindgroup, &[output_offset]);
+            pass.set_bind_group(1, pos_data.output_bindgroup.as_ref().unwrap(), &[]);
+            pass.dispatch_workgroups(count, count, count);
+
+            // exact distances near the surface over the update region, replacing the output while this update's
+            // coarse lists are live
+            if narrow_band != 0 {
+                if let Some(refine_pipeline) = pipeline_cache.get_compute_pipeline(pipeline.refine_pipeline) {
+                    pass.set_pipeline(refine_pipeline);
+                    pass.set_bind_group(0, working_bindgroup, &[*offset]);
+                    pass.set_bind_group(2, pos_data.dispatch_bindgroup.as_ref().unwrap(), &[]);
+                    pass.dispatch_workgroups(count, count, count);
+                }
+            }
+            stamp(&mut pass, StampIndex::OUTPUT);
+        }
+
+        // output cascades that only had a dynamic update
+        let static_outputs = pos_data.static_updates.iter().map(|update| update.output_offset).collect::<HashSet<_>>();
+        for offset in pos_data.output_updates.iter().filter(|offset| !static_outputs.contains(offset)) {
+            pass.set_pipeline(output_pipeline);
+            pass.set_bind_group(0, pos_data.working_bindgroup.as_ref().unwrap(), &[*offset]);
+            pass.set_bind_group(1, pos_data.output_bindgroup.as_ref().unwrap(), &[]);
+            pass.dispatch_workgroups(count, count, count);
+        }
 
+        // brute force verification of every cascade we output
+        let verify = pos_data.verify.as_ref().filter(|_| pos_data.verify_this_frame);
+        if let (Some(verify), Some(verify_pipeline)) = (verify, pipeline_cache.get_compute_pipeline(pipeline.verify_pipeline)) {
+            pass.set_pipeline(verify_pipeline);
+            pass.set_bind_group(1, pos_data.output_bindgroup.as_ref().unwrap(), &[]);
+            pass.set_bind_group(2, &verify.bindgroup, &[]);
+            for offset in pos_data.output_updates.iter() {
+                pass.set_bind_group(0, pos_data.working_bindgroup.as_ref().unwrap(), &[*offset]);
+                pass.dispatch_workgroups((tile_count + 63) / 64, 1, 1);
             }
         }
 
         drop(pass);
 
+        if let Some(verify) = verify {
+            render_context.command_encoder.copy_buffer_to_buffer(&verify.results, 0, &verify.readback, 0, VERIFY_RESULTS_SIZE);
+        }
+
         if pos_data.record_stats {
             if let Some((q, bs, ix)) = pos_data.time_query.as_ref() {
                 render_context.command_encoder.resolve_query_set(q, StampIndex::START as u32..StampIndex::OUTPUT as u32 + 1, &bs[*ix as usize], 0)
@@ -1270,4 +2874,56 @@ impl Node for SceneSdfNode {
 
         Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the scheduling systems only, with one cascade on `update_schedule`
+    fn scheduling_app(update_schedule: CascadeUpdateSchedule) -> App {
+        let mut app = App::new();
+        app
+        .insert_resource(SceneSdfSettings {
+            cascades: vec![CascadeSettings {
+                far_distance: 3.0,
+                update_schedule,
+                dynamic_update_schedule: CascadeUpdateSchedule::Paused,
+                truncation: None,
+            }],
+            ..default()
+        })
+        .init_resource::<SdfCentre>()
+        .init_resource::<FrameCount>()
+        .init_resource::<Time>()
+        .add_event::<SdfRebuild>()
+        .add_event::<AssetEvent<Mesh>>()
+        ;
+        setup_scheduling(&mut app);
+        app
+    }
+
+    // runs a frame, returns whether the cascade's static layer updated
+    fn updated(app: &mut App) -> bool {
+        app.update();
+        app.world.resource::<SdfState>().cascades[0].update_region.is_some()
+    }
+
+    #[test]
+    fn region_rebuild_runs_on_paused_cascades() {
+        let mut app = scheduling_app(CascadeUpdateSchedule::Paused);
+        // the first draw is always due
+        assert!(updated(&mut app));
+        assert!(!updated(&mut app));
+
+        let region = Aabb::from_min_max(Vec3::splat(-0.1), Vec3::splat(0.1));
+        app.world.resource_mut::<Events<SdfRebuild>>().send(SdfRebuild::Region(region));
+        assert!(updated(&mut app));
+        assert!(!updated(&mut app));
+
+        // regions outside the cascade don't make it due
+        let region = Aabb::from_min_max(Vec3::splat(100.0), Vec3::splat(101.0));
+        app.world.resource_mut::<Events<SdfRebuild>>().send(SdfRebuild::Region(region));
+        assert!(!updated(&mut app));
+    }
 }
\ No newline at end of file
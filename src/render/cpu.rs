@@ -0,0 +1,1116 @@
+// headless backend, see SdfBackend::Cpu
+// follows the gpu pipeline (transform -> coarse -> fine -> blend -> jump flood -> output) in tasks on the
+// AsyncComputeTaskPool, one batch of cascade updates at a time. seeds and flooded results are kept per cascade and
+// layer in toroidal grid coords like the seed and nearest textures, so scrolling only revoxelizes and floods the new
+// slab, then stitches it to the rest like jfa stitch.wgsl. the dynamic layer is flooded in full when it's redrawn
+
+use std::sync::{mpsc::{channel, TryRecvError}, Mutex};
+
+use bevy::tasks::{AsyncComputeTaskPool, ComputeTaskPool};
+
+use super::*;
+
+pub fn setup_cpu(app: &mut App) {
+    app
+    .init_resource::<CpuMeshes>()
+    .init_resource::<CpuWorker>()
+    .init_resource::<SdfCpuOutput>()
+    .add_system_to_stage(CoreStage::PostUpdate, calculate_cpu_bounds
+        .before(update_cascades)
+    )
+    .add_system_to_stage(CoreStage::PostUpdate, cache_cpu_meshes
+        .before(update_cascades)
+    )
+    .add_system_to_stage(CoreStage::PostUpdate, run_cpu_updates
+        .after(update_cascades)
+    )
+    .add_system(warn_ignored_settings)
+    ;
+}
+
+// once, the first time settings the cpu backend doesn't support are set
+fn warn_ignored_settings(settings: Res<SceneSdfSettings>, mut warned: Local<bool>) {
+    if *warned || !settings.is_changed() {
+        return;
+    }
+
+    let ignored: Vec<_> = [
+        (settings.sign != SdfSign::Unsigned, "sign (distances are unsigned)"),
+        (settings.narrow_band.is_some(), "narrow_band"),
+        (settings.output_format != SdfOutputFormat::R32Float, "output_format (SdfCpuOutput is always f32)"),
+    ].into_iter().filter_map(|(set, name)| set.then_some(name)).collect();
+    if !ignored.is_empty() {
+        warn!("the sdf cpu backend ignores {}", ignored.join(", "));
+        *warned = true;
+    }
+}
+
+// unsigned world space distances for every cascade, laid out like the gpu output texture: cascades side by side
+// along x, so a row of the volume is voxels_per_dim * cascade count values with x fastest, then y, then z.
+// distances are clamped to the cascade's `truncation` band when it is set. cascades that haven't finished their
+// first update read 0
+#[derive(Resource, Default, Clone)]
+pub struct SdfCpuOutput {
+    pub voxels_per_dim: u32,
+    pub cascades: Vec<SdfCpuCascade>,
+    pub distances: Vec<f32>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SdfCpuCascade {
+    // world position of the cascade's minimum corner
+    pub min: Vec3,
+    pub tile_size: f32,
+    pub voxel_size: f32,
+    pub ready: bool,
+}
+
+impl SdfCpuOutput {
+    // index into `distances` of a voxel within a cascade
+    pub fn index(&self, cascade: usize, voxel: UVec3) -> usize {
+        let v = self.voxels_per_dim as usize;
+        let width = v * self.cascades.len();
+        (voxel.z as usize * v + voxel.y as usize) * width + cascade * v + voxel.x as usize
+    }
+}
+
+// triangle lists of the mesh assets, shared with the tasks
+#[derive(Default)]
+struct CpuMesh {
+    positions: Vec<Vec3>,
+    indices: Vec<u32>,
+}
+
+#[derive(Resource, Default)]
+struct CpuMeshes(HashMap<Handle<Mesh>, Arc<CpuMesh>>);
+
+// same requirements as prepare_sdf_meshes: float32x3 positions, triangle lists
+fn cpu_mesh(mesh: &Mesh) -> Option<CpuMesh> {
+    let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+        return None;
+    };
+
+    let indices = match mesh.indices() {
+        Some(indices) => indices.iter().map(|i| i as u32).collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    Some(CpuMesh {
+        positions: positions.iter().map(|p| Vec3::from_array(*p)).collect(),
+        indices,
+    })
+}
+
+fn cache_cpu_meshes(
+    mut events: EventReader<AssetEvent<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+    mut cache: ResMut<CpuMeshes>,
+) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                match meshes.get(handle).and_then(cpu_mesh) {
+                    Some(mesh) => cache.0.insert(handle.clone_weak(), Arc::new(mesh)),
+                    None => cache.0.remove(handle),
+                };
+            }
+            AssetEvent::Removed { handle } => {
+                cache.0.remove(handle);
+            }
+        }
+    }
+}
+
+// headless apps don't have the VisibilityPlugin to add aabbs for us
+fn calculate_cpu_bounds(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    without_aabb: Query<(Entity, &Handle<Mesh>), Without<Aabb>>,
+) {
+    for (ent, handle) in &without_aabb {
+        if let Some(aabb) = meshes.get(handle).and_then(Mesh::compute_aabb) {
+            commands.entity(ent).insert(aabb);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct CpuInstance {
+    mesh: Arc<CpuMesh>,
+    transform: Mat4,
+    half_thickness: f32,
+}
+
+// one cascade update, the cpu side of SdfCascadeInfo plus the casters
+struct CpuJob {
+    cascade: usize,
+    origin: IVec3,
+    tile_size: f32,
+    truncation: Option<u32>,
+    // local tile region [min, max) of the static layer to revoxelize
+    static_region: Option<(IVec3, IVec3)>,
+    // local voxel region [min, max) to stitch after a scroll, see stitch_region
+    stitch: Option<(IVec3, IVec3)>,
+    static_instances: Vec<CpuInstance>,
+    // the dynamic layer is revoxelized in full when present
+    dynamic_instances: Option<Vec<CpuInstance>>,
+    verify: bool,
+}
+
+// subvoxel bits of the non-empty voxels, keyed by grid voxel index
+#[derive(Default)]
+struct CpuCascade {
+    static_seeds: HashMap<u32, u128>,
+    dynamic_seeds: HashMap<u32, u128>,
+    // flooded layers indexed by grid voxel, empty until the layer is first drawn
+    static_nearest: Vec<Nearest>,
+    dynamic_nearest: Vec<Nearest>,
+}
+
+struct CpuCascadeOutput {
+    cascade: usize,
+    origin: IVec3,
+    tile_size: f32,
+    // truncation band in world units, 0 for unclamped
+    band: f32,
+    // local voxels, x fastest
+    distances: Vec<f32>,
+    // max error, failures, samples, see verify.wgsl
+    verify: Option<(f32, u32, u32)>,
+}
+
+struct CpuResult {
+    cascades: Vec<CpuCascade>,
+    outputs: Vec<CpuCascadeOutput>,
+}
+
+#[derive(Resource, Default)]
+struct CpuWorker {
+    // SdfState::generation the seeds were built for
+    generation: u32,
+    resolution: SdfResolution,
+    voxelization: SdfVoxelization,
+    pending: Vec<CpuJob>,
+    // None while a task owns them
+    cascades: Option<Vec<CpuCascade>>,
+    in_flight: Option<Mutex<Receiver<CpuResult>>>,
+}
+
+fn run_cpu_updates(
+    state: Res<SdfState>,
+    meshes: Res<CpuMeshes>,
+    casters: Query<(&Handle<Mesh>, &GlobalTransform, Option<&SdfMinThickness>)>,
+    mut worker: ResMut<CpuWorker>,
+    mut output: ResMut<SdfCpuOutput>,
+    mut scene_sdf: ResMut<SceneSdf>,
+    mut rebuilds: EventWriter<SdfRebuild>,
+) {
+    // start from scratch when the settings change, any running task's results are dropped with the receiver
+    if (worker.cascades.is_none() && worker.in_flight.is_none()) || worker.generation != state.generation {
+        *worker = CpuWorker {
+            generation: state.generation,
+            resolution: state.resolution.clone(),
+            voxelization: state.voxelization,
+            pending: Vec::new(),
+            cascades: Some(state.cascades.iter().map(|_| CpuCascade::default()).collect()),
+            in_flight: None,
+        };
+    }
+    // the outputs keep the old distances until the cascades are redrawn, unless the layout changed
+    if scene_sdf.generation != state.layout_generation {
+        let voxels_per_dim = state.resolution.voxels_per_dim();
+        *output = SdfCpuOutput {
+            voxels_per_dim,
+            cascades: vec![SdfCpuCascade::default(); state.cascades.len()],
+            distances: vec![0.0; voxels_per_dim.pow(3) as usize * state.cascades.len()],
+        };
+        scene_sdf.reset(state.layout_generation, voxels_per_dim, state.cascades.len());
+    }
+
+    // collect finished updates
+    let received = worker.in_flight.as_ref().map(|receiver| receiver.lock().unwrap().try_recv());
+    match received {
+        None | Some(Err(TryRecvError::Empty)) => (),
+        Some(Ok(result)) => {
+            worker.in_flight = None;
+            worker.cascades = Some(result.cascades);
+
+            let v = output.voxels_per_dim as usize;
+            let voxels_per_tile_dim = worker.resolution.voxels_per_tile_dim as f32;
+            for cascade_output in result.outputs {
+                let c = cascade_output.cascade;
+                for (row, distances) in cascade_output.distances.chunks(v).enumerate() {
+                    let start = output.index(c, UVec3::new(0, (row % v) as u32, (row / v) as u32));
+                    output.distances[start..start + v].copy_from_slice(distances);
+                }
+                let cascade = SdfCpuCascade {
+                    min: cascade_output.origin.as_vec3() * cascade_output.tile_size,
+                    tile_size: cascade_output.tile_size,
+                    voxel_size: cascade_output.tile_size / voxels_per_tile_dim,
+                    ready: true,
+                };
+                scene_sdf.set_cascade(c, MirroredCascade {
+                    min: cascade.min,
+                    voxel_size: cascade.voxel_size,
+                    band: cascade_output.band,
+                    distances: cascade_output.distances.into(),
+                });
+                output.cascades[c] = cascade;
+
+                if let Some((max_error, failures, samples)) = cascade_output.verify {
+                    if failures > 0 {
+                        warn!("sdf verify (cpu): cascade {c}, {samples} samples, max overestimate {max_error:.3} voxels, {failures} over half a voxel");
+                    } else {
+                        info!("sdf verify (cpu): cascade {c}, {samples} samples, max overestimate {max_error:.3} voxels");
+                    }
+                }
+            }
+        }
+        Some(Err(TryRecvError::Disconnected)) => {
+            // the task panicked, its seeds are gone
+            error!("sdf cpu update failed, redrawing all cascades");
+            worker.in_flight = None;
+            worker.pending.clear();
+            worker.cascades = Some(state.cascades.iter().map(|_| CpuCascade::default()).collect());
+            rebuilds.send(SdfRebuild::All);
+        }
+    }
+
+    // queue this frame's updates
+    let instances = |entities: &HashSet<Entity>| -> Vec<CpuInstance> {
+        entities.iter().filter_map(|ent| {
+            let (handle, g_trans, thickness) = casters.get(*ent).ok()?;
+            Some(CpuInstance {
+                // not loaded yet. the cascade is marked dirty when it is
+                mesh: meshes.0.get(handle)?.clone(),
+                transform: g_trans.compute_matrix(),
+                half_thickness: half_thickness(thickness),
+            })
+        }).collect()
+    };
+
+    for (i, cascade) in state.cascades.iter().enumerate() {
+        let Some(origin) = cascade.last_origin else {
+            continue;
+        };
+        if cascade.update_region.is_none() && !cascade.dynamic_redraw {
+            continue;
+        }
+
+        if cascade.dynamic_redraw {
+            // replaces any dynamic redraw that hasn't started yet
+            for job in worker.pending.iter_mut().filter(|job| job.cascade == i) {
+                job.dynamic_instances = None;
+            }
+            worker.pending.retain(|job| job.static_region.is_some() || job.dynamic_instances.is_some());
+        }
+
+        let job = CpuJob {
+            cascade: i,
+            origin,
+            tile_size: cascade.tile_size(),
+            truncation: cascade.settings.truncation,
+            static_region: cascade.update_region,
+            stitch: state.stitch_region(cascade),
+            static_instances: match cascade.update_region {
+                Some(_) => instances(&state.visible_entities),
+                None => Vec::new(),
+            },
+            dynamic_instances: cascade.dynamic_redraw.then(|| instances(&state.dynamic_entities)),
+            verify: state.verify,
+        };
+        worker.pending.push(job);
+    }
+
+    // start the next batch
+    if worker.in_flight.is_none() && !worker.pending.is_empty() {
+        let jobs = std::mem::take(&mut worker.pending);
+        let mut cascades = worker.cascades.take().unwrap();
+        let resolution = worker.resolution.clone();
+        let voxelization = worker.voxelization;
+        let (sender, receiver) = channel();
+
+        AsyncComputeTaskPool::get().spawn(async move {
+            let outputs = jobs
+                .into_iter()
+                .map(|job| run_job(job, &mut cascades[..], &resolution, voxelization))
+                .collect();
+            // fails if the settings changed while we were running
+            let _ = sender.send(CpuResult { cascades, outputs });
+        }).detach();
+
+        worker.in_flight = Some(Mutex::new(receiver));
+    }
+}
+
+// cascade space triangle with the caster's thickness, see TransformedTri in types.wgsl
+struct CpuTri {
+    v: [Vec3; 3],
+    half_thickness: f32,
+}
+
+// transform.wgsl
+fn transform_tris(instances: &[CpuInstance], offset: Vec3) -> Vec<CpuTri> {
+    let mut tris = Vec::new();
+    for instance in instances {
+        let positions = &instance.mesh.positions;
+        for tri in instance.mesh.indices.chunks_exact(3) {
+            let [Some(a), Some(b), Some(c)] = [tri[0], tri[1], tri[2]].map(|i| positions.get(i as usize)) else {
+                continue;
+            };
+            let v = [a, b, c].map(|p| instance.transform.transform_point3(*p) - offset);
+            tris.push(CpuTri { v, half_thickness: instance.half_thickness });
+        }
+    }
+    tris
+}
+
+const SAT_AXIS_COUNT: usize = 10;
+
+// see intersect::TriAccel
+struct TriAccel {
+    min: Vec3,
+    max: Vec3,
+    dominant: usize,
+    sat_axes: [Vec3; SAT_AXIS_COUNT],
+    sat_ranges: [Vec2; SAT_AXIS_COUNT],
+}
+
+impl TriAccel {
+    // intersect::build_tri
+    fn new(tri: &CpuTri, tile_size: f32, subvoxel_size: f32, voxelization: SdfVoxelization) -> Self {
+        let v = tri.v;
+        let e = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+        let cross_e0_e1 = e[0].cross(e[1]);
+
+        let abs_n = cross_e0_e1.abs();
+        let dominant = if abs_n.x >= abs_n.y && abs_n.x >= abs_n.z {
+            0
+        } else if abs_n.y >= abs_n.z {
+            1
+        } else {
+            2
+        };
+
+        let radius = 0.5 * subvoxel_size + 1e-5 * tile_size;
+        let thickness = cross_e0_e1 * (tri.half_thickness / cross_e0_e1.length().max(1e-20));
+        let plane_diamond = voxelization == SdfVoxelization::SixSeparating;
+        let edge_diamond = voxelization != SdfVoxelization::Conservative;
+
+        let mut axes = [(cross_e0_e1, plane_diamond); SAT_AXIS_COUNT];
+        for (i, e) in e.iter().enumerate() {
+            axes[1 + i * 3] = (Vec3::new(0.0, e.z, -e.y), edge_diamond);
+            axes[2 + i * 3] = (Vec3::new(-e.z, 0.0, e.x), edge_diamond);
+            axes[3 + i * 3] = (Vec3::new(e.y, -e.x, 0.0), edge_diamond);
+        }
+
+        let sat_axes = axes.map(|(axis, _)| axis);
+        let sat_ranges = axes.map(|(axis, diamond)| {
+            let support = match diamond {
+                true => radius * axis.abs().max_element(),
+                false => radius * axis.abs().dot(Vec3::ONE),
+            };
+            let r = support + axis.dot(thickness).abs();
+            let p = v.map(|v| axis.dot(v));
+            Vec2::new(p[0].min(p[1]).min(p[2]) - r, p[0].max(p[1]).max(p[2]) + r)
+        });
+
+        Self {
+            min: v[0].min(v[1]).min(v[2]) - tri.half_thickness,
+            max: v[0].max(v[1]).max(v[2]) + tri.half_thickness,
+            dominant,
+            sat_axes,
+            sat_ranges,
+        }
+    }
+
+    // intersect::subvoxel_span
+    fn subvoxel_span(&self, tile_min: Vec3, subvoxel: UVec3, subv: f32, count: u32) -> (u32, u32) {
+        let d = self.dominant;
+        let mut row_start = subvoxel;
+        row_start[d] = 0;
+        let base_point = tile_min + (row_start.as_vec3() + 0.5) * subv;
+
+        let mut low = 0.0f32;
+        let mut high = count as f32 - 1.0;
+
+        for (axis, range) in self.sat_axes.iter().zip(&self.sat_ranges) {
+            let base = axis.dot(base_point);
+            let slope = axis[d] * subv;
+
+            if slope.abs() < 1e-20 {
+                if base < range.x || base > range.y {
+                    return (0, 0);
+                }
+            } else {
+                let t0 = (range.x - base) / slope;
+                let t1 = (range.y - base) / slope;
+                low = low.max(t0.min(t1));
+                high = high.min(t0.max(t1));
+            }
+        }
+
+        if low > high {
+            return (0, 0);
+        }
+
+        (low.ceil() as u32, high.floor() as u32 + 1)
+    }
+}
+
+// grid and local addressing for one cascade, see addressing.wgsl
+struct CascadeSpace {
+    origin: IVec3,
+    tile_dim_count: i32,
+    voxels_per_tile_dim: i32,
+    voxels_per_dim: i32,
+    subvoxels_per_voxel_dim: u32,
+    tile_size: f32,
+    voxel_size: f32,
+    subvoxel_size: f32,
+}
+
+impl CascadeSpace {
+    fn new(origin: IVec3, tile_size: f32, resolution: &SdfResolution) -> Self {
+        let voxel_size = tile_size / resolution.voxels_per_tile_dim as f32;
+        Self {
+            origin,
+            tile_dim_count: resolution.tile_dim_count as i32,
+            voxels_per_tile_dim: resolution.voxels_per_tile_dim as i32,
+            voxels_per_dim: resolution.voxels_per_dim() as i32,
+            subvoxels_per_voxel_dim: resolution.subvoxels_per_voxel_dim,
+            tile_size,
+            voxel_size,
+            subvoxel_size: voxel_size / resolution.subvoxels_per_voxel_dim as f32,
+        }
+    }
+
+    fn local_index(&self, local_voxel: IVec3) -> usize {
+        ((local_voxel.z * self.voxels_per_dim + local_voxel.y) * self.voxels_per_dim + local_voxel.x) as usize
+    }
+
+    fn local_voxel(&self, index: usize) -> IVec3 {
+        let v = self.voxels_per_dim as usize;
+        IVec3::new((index % v) as i32, ((index / v) % v) as i32, (index / (v * v)) as i32)
+    }
+
+    // addr::mod3i
+    fn wrap_tile(&self, tile: IVec3) -> IVec3 {
+        let n = self.tile_dim_count;
+        IVec3::new(tile.x.rem_euclid(n), tile.y.rem_euclid(n), tile.z.rem_euclid(n))
+    }
+
+    // addr::voxel_local_to_grid, as an index
+    fn grid_key(&self, local_voxel: IVec3) -> u32 {
+        let local_tile = local_voxel / self.voxels_per_tile_dim;
+        let tile_voxel = local_voxel % self.voxels_per_tile_dim;
+        let grid = self.wrap_tile(self.origin + local_tile) * self.voxels_per_tile_dim + tile_voxel;
+        ((grid.z * self.voxels_per_dim + grid.y) * self.voxels_per_dim + grid.x) as u32
+    }
+
+    // addr::voxel_grid_to_local
+    fn grid_key_to_local(&self, key: u32) -> IVec3 {
+        let grid = self.local_voxel(key as usize);
+        let tile_offset = self.wrap_tile(IVec3::splat(self.tile_dim_count) - self.origin);
+        (grid + tile_offset * self.voxels_per_tile_dim) % self.voxels_per_dim
+    }
+
+    fn voxel_position(&self, local_voxel: IVec3) -> Vec3 {
+        (local_voxel.as_vec3() + 0.5) * self.voxel_size
+    }
+
+    // addr::subvoxel_local_position with the subvoxel index
+    fn subvoxel_position(&self, local_voxel: IVec3, shift: u32) -> Vec3 {
+        let s = self.subvoxels_per_voxel_dim;
+        let subvoxel = UVec3::new(shift % s, (shift / s) % s, shift / (s * s));
+        ((local_voxel * s as i32 + subvoxel.as_ivec3()).as_vec3() + 0.5) * self.subvoxel_size
+    }
+
+    fn half_diagonal(&self) -> f32 {
+        0.8660254 * self.subvoxel_size
+    }
+}
+
+// bit indices of a seed
+fn subvoxels(mut bits: u128) -> impl Iterator<Item = u32> {
+    std::iter::from_fn(move || {
+        (bits != 0).then(|| {
+            let shift = bits.trailing_zeros();
+            bits &= bits - 1;
+            shift
+        })
+    })
+}
+
+// coarse.wgsl + fine.wgsl + fine_blend.wgsl for one layer, replacing the seeds within the region
+fn voxelize(
+    space: &CascadeSpace,
+    region: (IVec3, IVec3),
+    instances: &[CpuInstance],
+    seeds: &mut HashMap<u32, u128>,
+    voxelization: SdfVoxelization,
+) {
+    let (region_min, region_max) = region;
+    let region_size = region_max - region_min;
+
+    if region_size == IVec3::splat(space.tile_dim_count) {
+        seeds.clear();
+    } else {
+        let min_voxel = region_min * space.voxels_per_tile_dim;
+        let max_voxel = region_max * space.voxels_per_tile_dim;
+        for z in min_voxel.z..max_voxel.z {
+            for y in min_voxel.y..max_voxel.y {
+                for x in min_voxel.x..max_voxel.x {
+                    seeds.remove(&space.grid_key(IVec3::new(x, y, z)));
+                }
+            }
+        }
+    }
+
+    let offset = space.origin.as_vec3() * space.tile_size;
+    let tris = transform_tris(instances, offset)
+        .iter()
+        .map(|tri| TriAccel::new(tri, space.tile_size, space.subvoxel_size, voxelization))
+        .collect::<Vec<_>>();
+
+    // coarse bins over the region
+    let epsilon = 1e-5 * space.tile_size;
+    let bin_index = |tile: IVec3| {
+        let t = tile - region_min;
+        ((t.z * region_size.y + t.y) * region_size.x + t.x) as usize
+    };
+    let mut bins = vec![Vec::new(); (region_size.x * region_size.y * region_size.z) as usize];
+    for (i, tri) in tris.iter().enumerate() {
+        let min = (tri.min / space.tile_size).floor().as_ivec3().max(region_min);
+        let max = ((tri.max + epsilon) / space.tile_size).ceil().as_ivec3().min(region_max);
+        for z in min.z..max.z {
+            for y in min.y..max.y {
+                for x in min.x..max.x {
+                    bins[bin_index(IVec3::new(x, y, z))].push(i as u32);
+                }
+            }
+        }
+    }
+
+    // fine raster, a task per occupied tile
+    let tris = &tris;
+    let rastered = ComputeTaskPool::get().scope(|scope| {
+        for z in region_min.z..region_max.z {
+            for y in region_min.y..region_max.y {
+                for x in region_min.x..region_max.x {
+                    let tile = IVec3::new(x, y, z);
+                    let ids = &bins[bin_index(tile)];
+                    if ids.is_empty() {
+                        continue;
+                    }
+                    scope.spawn(async move { (tile, raster_tile(space, tile, tris, ids)) });
+                }
+            }
+        }
+    });
+
+    for (tile, bits) in rastered {
+        let vptd = space.voxels_per_tile_dim;
+        for (index, bits) in bits.into_iter().enumerate() {
+            if bits == 0 {
+                continue;
+            }
+            let index = index as i32;
+            let tile_voxel = IVec3::new(index % vptd, (index / vptd) % vptd, index / (vptd * vptd));
+            seeds.insert(space.grid_key(tile * vptd + tile_voxel), bits);
+        }
+    }
+}
+
+// fine.wgsl, subvoxel bits for each voxel of the tile
+fn raster_tile(space: &CascadeSpace, tile: IVec3, tris: &[TriAccel], ids: &[u32]) -> Vec<u128> {
+    let vptd = space.voxels_per_tile_dim as u32;
+    let s = space.subvoxels_per_voxel_dim;
+    let count = vptd * s;
+    let subv = space.subvoxel_size;
+    let tile_min = tile.as_vec3() * space.tile_size;
+    let epsilon = 1e-5 * space.tile_size;
+
+    let mut voxel_bits = vec![0u128; vptd.pow(3) as usize];
+    for id in ids {
+        let tri = &tris[*id as usize];
+        let bb_min = ((tri.min - tile_min) / subv).floor().as_ivec3().max(IVec3::ZERO).as_uvec3();
+        let bb_max = ((tri.max - tile_min + epsilon) / subv).ceil().as_ivec3().min(IVec3::splat(count as i32)).max(IVec3::ZERO).as_uvec3();
+
+        let d = tri.dominant;
+        let a = (d + 1) % 3;
+        let b = (d + 2) % 3;
+
+        for i in bb_min[a]..bb_max[a] {
+            for j in bb_min[b]..bb_max[b] {
+                let mut pos = UVec3::ZERO;
+                pos[a] = i;
+                pos[b] = j;
+
+                let (start, end) = tri.subvoxel_span(tile_min, pos, subv, count);
+                for k in bb_min[d].max(start)..bb_max[d].min(end) {
+                    pos[d] = k;
+                    let voxel = pos / s;
+                    let subvoxel = pos % s;
+                    let index = (voxel.z * vptd + voxel.y) * vptd + voxel.x;
+                    let shift = (subvoxel.z * s + subvoxel.y) * s + subvoxel.x;
+                    voxel_bits[index as usize] |= 1 << shift;
+                }
+            }
+        }
+    }
+    voxel_bits
+}
+
+// nearest seed found so far: the seed voxel's offset from the voxel and the seed subvoxel, like nearest_jfa.
+// offsets don't change when the cascade scrolls, so the flooded layers are kept by grid voxel between updates
+type Nearest = Option<(IVec3, u32)>;
+
+// a layer's seeds in local coords
+fn local_seeds(space: &CascadeSpace, seeds: &HashMap<u32, u128>) -> Vec<u128> {
+    let mut local = vec![0u128; (space.voxels_per_dim as usize).pow(3)];
+    for (key, bits) in seeds.iter() {
+        local[space.local_index(space.grid_key_to_local(*key))] = *bits;
+    }
+    local
+}
+
+// a flooded layer from grid to local order
+fn nearest_to_local(space: &CascadeSpace, grid: &[Nearest]) -> Vec<Nearest> {
+    (0..grid.len()).map(|index| grid[space.grid_key(space.local_voxel(index)) as usize]).collect()
+}
+
+// and back
+fn nearest_to_grid(space: &CascadeSpace, local: &[Nearest]) -> Vec<Nearest> {
+    let mut grid = vec![None; local.len()];
+    for (index, nearest) in local.iter().enumerate() {
+        grid[space.grid_key(space.local_voxel(index)) as usize] = *nearest;
+    }
+    grid
+}
+
+// seed cells start at their own subvoxel nearest the centre, and are not revisited
+fn seed_nearest(space: &CascadeSpace, local_voxel: IVec3, bits: u128) -> Nearest {
+    let target = space.voxel_position(local_voxel);
+    subvoxels(bits)
+        .map(|shift| (shift, target.distance_squared(space.subvoxel_position(local_voxel, shift))))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(shift, _)| (IVec3::ZERO, shift))
+}
+
+// jfa simple.wgsl over the whole cascade
+fn flood_all(space: &CascadeSpace, seeds: &[u128]) -> Vec<Nearest> {
+    let nearest = seeds.iter().enumerate().map(|(index, bits)| seed_nearest(space, space.local_voxel(index), *bits)).collect();
+    let all = (IVec3::ZERO, IVec3::splat(space.voxels_per_dim));
+    jump_flood(space, seeds, nearest, all, None)
+}
+
+// jump passes over the local voxels [min, max), reading sources from the whole cascade. see jfa simple.wgsl, and
+// jfa stitch.wgsl when the scrolled-in tile region `slab` is given
+fn jump_flood(space: &CascadeSpace, seeds: &[u128], mut current: Vec<Nearest>, voxels: (IVec3, IVec3), slab: Option<(IVec3, IVec3)>) -> Vec<Nearest> {
+    let mut next = current.clone();
+
+    let (min, max) = voxels;
+    let v = space.voxels_per_dim;
+    let plane = (v * v) as usize;
+    let mut jump_size = v / 2;
+    while jump_size > 0 {
+        // voxels we skip keep their value
+        next.copy_from_slice(&current);
+        let (current_ref, jump) = (&current, jump_size);
+        ComputeTaskPool::get().scope(|scope| {
+            for (z, slice) in next.chunks_mut(plane).enumerate().take(max.z as usize).skip(min.z as usize) {
+                scope.spawn(async move {
+                    for y in min.y..max.y {
+                        for x in min.x..max.x {
+                            let local_voxel = IVec3::new(x, y, z as i32);
+                            let index = space.local_index(local_voxel);
+                            if seeds[index] != 0 {
+                                continue;
+                            }
+                            if let Some(slab) = slab {
+                                if stitch_skips(space, slab, local_voxel, current_ref[index]) {
+                                    continue;
+                                }
+                            }
+                            slice[index - z * plane] = flood_voxel(space, seeds, current_ref, local_voxel, jump);
+                        }
+                    }
+                });
+            }
+        });
+        std::mem::swap(&mut current, &mut next);
+        jump_size /= 2;
+    }
+
+    current
+}
+
+// distance from a voxel centre to its current seed
+fn nearest_distance_sq(space: &CascadeSpace, local_voxel: IVec3, nearest: Nearest) -> Option<f32> {
+    nearest.map(|(offset, shift)| space.voxel_position(local_voxel).distance_squared(space.subvoxel_position(local_voxel + offset, shift)))
+}
+
+// jfa stitch.wgsl: no slab seed can beat a seed that's closer than the slab itself
+fn stitch_skips(space: &CascadeSpace, slab: (IVec3, IVec3), local_voxel: IVec3, nearest: Nearest) -> bool {
+    let point = space.voxel_position(local_voxel);
+    let slab_min = slab.0.as_vec3() * space.tile_size;
+    let slab_max = slab.1.as_vec3() * space.tile_size;
+    let slab_dist = (slab_min - point).max(point - slab_max).max(Vec3::ZERO).length();
+    matches!(nearest_distance_sq(space, local_voxel, nearest), Some(dist_sq) if dist_sq <= slab_dist * slab_dist)
+}
+
+// best of the voxel's own nearest and its 26 neighbours at the jump distance
+fn flood_voxel(space: &CascadeSpace, seeds: &[u128], nearest: &[Nearest], local_voxel: IVec3, jump: i32) -> Nearest {
+    let target = space.voxel_position(local_voxel);
+
+    let mut best = nearest[space.local_index(local_voxel)];
+    let mut best_dist_sq = nearest_distance_sq(space, local_voxel, best).unwrap_or(f32::MAX);
+
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                let source = local_voxel + IVec3::new(x, y, z) * jump;
+                if (x, y, z) == (0, 0, 0) || source.min_element() < 0 || source.max_element() >= space.voxels_per_dim {
+                    continue;
+                }
+
+                let source_index = space.local_index(source);
+                let Some((offset, shift)) = nearest[source_index] else {
+                    continue;
+                };
+
+                let seed_voxel = source + offset;
+                let mut check = |shift| {
+                    let dist_sq = target.distance_squared(space.subvoxel_position(seed_voxel, shift));
+                    if dist_sq < best_dist_sq {
+                        best_dist_sq = dist_sq;
+                        best = Some((seed_voxel - local_voxel, shift));
+                    }
+                };
+
+                if offset == IVec3::ZERO {
+                    // the source is a seed, check all of its subvoxels
+                    subvoxels(seeds[source_index]).for_each(&mut check);
+                } else {
+                    check(shift);
+                }
+            }
+        }
+    }
+
+    best
+}
+
+fn run_job(job: CpuJob, cascades: &mut [CpuCascade], resolution: &SdfResolution, voxelization: SdfVoxelization) -> CpuCascadeOutput {
+    let space = CascadeSpace::new(job.origin, job.tile_size, resolution);
+    let cascade = &mut cascades[job.cascade];
+
+    if let Some(region) = job.static_region {
+        voxelize(&space, region, &job.static_instances, &mut cascade.static_seeds, voxelization);
+        let seeds = local_seeds(&space, &cascade.static_seeds);
+
+        let nearest = match job.stitch.filter(|_| !cascade.static_nearest.is_empty()) {
+            Some(stitch) => {
+                // flood the slab from its own seeds and everything we kept, then stitch it to the kept voxels near it
+                let slab = (region.0 * space.voxels_per_tile_dim, region.1 * space.voxels_per_tile_dim);
+                let mut nearest = nearest_to_local(&space, &cascade.static_nearest);
+                for z in slab.0.z..slab.1.z {
+                    for y in slab.0.y..slab.1.y {
+                        for x in slab.0.x..slab.1.x {
+                            let local_voxel = IVec3::new(x, y, z);
+                            let index = space.local_index(local_voxel);
+                            nearest[index] = seed_nearest(&space, local_voxel, seeds[index]);
+                        }
+                    }
+                }
+                let nearest = jump_flood(&space, &seeds, nearest, slab, None);
+                jump_flood(&space, &seeds, nearest, stitch, Some(region))
+            }
+            // full redraws and in-place rebuilds flood the whole cascade
+            None => flood_all(&space, &seeds),
+        };
+        cascade.static_nearest = nearest_to_grid(&space, &nearest);
+    }
+
+    if let Some(instances) = job.dynamic_instances.as_ref() {
+        let all = (IVec3::ZERO, IVec3::splat(space.tile_dim_count));
+        voxelize(&space, all, instances, &mut cascade.dynamic_seeds, voxelization);
+        let seeds = local_seeds(&space, &cascade.dynamic_seeds);
+        cascade.dynamic_nearest = nearest_to_grid(&space, &flood_all(&space, &seeds));
+    }
+
+    // output.wgsl, merging the layers
+    let half_diagonal = space.half_diagonal();
+    let no_seed = space.tile_size * (space.tile_dim_count - 1) as f32;
+    let band = job.truncation.map_or(0.0, |truncation| truncation.max(1) as f32 * space.voxel_size);
+    let distances = (0..(space.voxels_per_dim as usize).pow(3)).map(|index| {
+        let local_voxel = space.local_voxel(index);
+        let key = space.grid_key(local_voxel) as usize;
+        let dist_sq = [&cascade.static_nearest, &cascade.dynamic_nearest]
+            .iter()
+            .filter_map(|layer| nearest_distance_sq(&space, local_voxel, *layer.get(key)?))
+            .min_by(f32::total_cmp);
+        let dist = dist_sq.map_or(no_seed, f32::sqrt) - half_diagonal;
+        match band > 0.0 {
+            true => dist.min(band),
+            false => dist,
+        }
+    }).collect::<Vec<_>>();
+
+    let verify = job.verify.then(|| {
+        let mut seeds = local_seeds(&space, &cascade.static_seeds);
+        for (seed, dynamic) in seeds.iter_mut().zip(local_seeds(&space, &cascade.dynamic_seeds)) {
+            *seed |= dynamic;
+        }
+        verify(&space, &seeds, &distances)
+    });
+
+    CpuCascadeOutput {
+        cascade: job.cascade,
+        origin: job.origin,
+        tile_size: job.tile_size,
+        band,
+        distances,
+        verify,
+    }
+}
+
+// verify.wgsl, returns max error, failures and samples
+fn verify(space: &CascadeSpace, seeds: &[u128], distances: &[f32]) -> (f32, u32, u32) {
+    const VERIFY_RADIUS: i32 = 12;
+    const VERIFY_TOLERANCE: f32 = 0.5;
+
+    fn hash(x: u32) -> u32 {
+        let state = x.wrapping_mul(747796405).wrapping_add(2891336453);
+        let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+        (word >> 22) ^ word
+    }
+
+    let t = space.tile_dim_count;
+    let vptd = space.voxels_per_tile_dim as u32;
+    let origin = space.origin.as_uvec3();
+    let half_diagonal = space.half_diagonal();
+
+    let (mut max_error, mut failures, mut samples) = (0.0f32, 0, 0);
+    for tile in 0..(t * t * t) as u32 {
+        // pick a different voxel whenever the cascade moves
+        let voxel_index = hash(tile ^ hash(origin.x ^ hash(origin.y ^ hash(origin.z)))) % vptd.pow(3);
+        let tile_voxel = UVec3::new(voxel_index % vptd, (voxel_index / vptd) % vptd, voxel_index / (vptd * vptd));
+        let local_tile = UVec3::new(tile % t as u32, (tile / t as u32) % t as u32, tile / (t * t) as u32);
+        let local_voxel = (local_tile * vptd + tile_voxel).as_ivec3();
+        let target = space.voxel_position(local_voxel);
+        let output_dist = distances[space.local_index(local_voxel)];
+
+        let radius = (((output_dist + half_diagonal) / space.voxel_size).ceil() as i32 + 1).min(VERIFY_RADIUS);
+        let mut best_dist_sq = None::<f32>;
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                for z in -radius..=radius {
+                    let seed_voxel = local_voxel + IVec3::new(x, y, z);
+                    if seed_voxel.min_element() < 0 || seed_voxel.max_element() >= space.voxels_per_dim {
+                        continue;
+                    }
+                    for shift in subvoxels(seeds[space.local_index(seed_voxel)]) {
+                        let dist_sq = target.distance_squared(space.subvoxel_position(seed_voxel, shift));
+                        best_dist_sq = Some(best_dist_sq.map_or(dist_sq, |best| best.min(dist_sq)));
+                    }
+                }
+            }
+        }
+
+        samples += 1;
+        let Some(best_dist_sq) = best_dist_sq else {
+            continue;
+        };
+
+        let error = (output_dist - (best_dist_sq.sqrt() - half_diagonal)) / space.voxel_size;
+        max_error = max_error.max(error);
+        if error > VERIFY_TOLERANCE {
+            failures += 1;
+        }
+    }
+
+    (max_error, failures, samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::TaskPool;
+
+    use super::*;
+
+    const TILE_SIZE: f32 = 1.0;
+
+    // 32 voxels per dim, 2x2x2 subvoxels
+    fn resolution() -> SdfResolution {
+        SdfResolution::new(4, 8, 2)
+    }
+
+    // a tilted quad and a tetrahedron spanning x 1.3 - 4.6, so only inside the cascade once it scrolls a tile along x
+    fn instance() -> CpuInstance {
+        let positions = vec![
+            Vec3::new(1.3, 0.6, 0.8),
+            Vec3::new(4.6, 1.1, 0.9),
+            Vec3::new(4.4, 1.9, 3.2),
+            Vec3::new(1.5, 1.4, 3.1),
+            Vec3::new(2.5, 2.4, 1.2),
+            Vec3::new(3.6, 2.6, 1.5),
+            Vec3::new(3.0, 3.4, 1.4),
+            Vec3::new(3.1, 2.9, 2.6),
+        ];
+        let indices = vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7, 4, 7, 5, 5, 7, 6];
+        CpuInstance {
+            mesh: Arc::new(CpuMesh { positions, indices }),
+            transform: Mat4::IDENTITY,
+            half_thickness: 0.0,
+        }
+    }
+
+    fn job(origin: IVec3, static_region: (IVec3, IVec3), stitch: Option<(IVec3, IVec3)>, truncation: Option<u32>) -> CpuJob {
+        CpuJob {
+            cascade: 0,
+            origin,
+            tile_size: TILE_SIZE,
+            truncation,
+            static_region: Some(static_region),
+            stitch,
+            static_instances: vec![instance()],
+            dynamic_instances: None,
+            verify: false,
+        }
+    }
+
+    // real time collision detection 5.1.5
+    fn closest_point_on_tri(p: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+        let (ab, ac, ap) = (b - a, c - a, p - a);
+        let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return a;
+        }
+        let bp = p - b;
+        let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+        if d3 >= 0.0 && d4 <= d3 {
+            return b;
+        }
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            return a + ab * (d1 / (d1 - d3));
+        }
+        let cp = p - c;
+        let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+        if d6 >= 0.0 && d5 <= d6 {
+            return c;
+        }
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            return a + ac * (d2 / (d2 - d6));
+        }
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+            return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+        }
+        let denom = 1.0 / (va + vb + vc);
+        a + ab * (vb * denom) + ac * (vc * denom)
+    }
+
+    // every voxel within half a voxel of the exact triangle distance less the subvoxel half diagonal, which is
+    // what the seeds can resolve
+    fn check_output(output: &CpuCascadeOutput, instances: &[CpuInstance]) {
+        let space = CascadeSpace::new(output.origin, output.tile_size, &resolution());
+        let tris = transform_tris(instances, space.origin.as_vec3() * space.tile_size);
+
+        let mut worst = 0.0f32;
+        for (index, distance) in output.distances.iter().enumerate() {
+            let point = space.voxel_position(space.local_voxel(index));
+            let exact = tris
+                .iter()
+                .map(|tri| point.distance(closest_point_on_tri(point, tri.v[0], tri.v[1], tri.v[2])))
+                .fold(f32::MAX, f32::min);
+            let mut expected = exact - space.half_diagonal();
+            if output.band > 0.0 {
+                expected = expected.min(output.band);
+            }
+            worst = worst.max((distance - expected).abs() / space.voxel_size);
+        }
+        assert!(worst <= 0.5, "error up to {worst} voxels");
+    }
+
+    // full redraw at `from`, then scroll by `redraw` tiles into a slab containing part of the mesh
+    fn scroll(from: IVec3, redraw: IVec3, truncation: Option<u32>) {
+        ComputeTaskPool::init(TaskPool::default);
+        let resolution = resolution();
+        let mut cascades = vec![CpuCascade::default()];
+        let tile_dim_count = IVec3::splat(4);
+        run_job(job(from, (IVec3::ZERO, tile_dim_count), None, truncation), &mut cascades, &resolution, SdfVoxelization::Conservative);
+
+        // as update_cascades
+        let slab = (
+            IVec3::select(redraw.cmpgt(IVec3::ZERO), tile_dim_count - redraw, IVec3::ZERO),
+            IVec3::select(redraw.cmplt(IVec3::ZERO), -redraw, tile_dim_count),
+        );
+        let stitch = stitch_region(&resolution, redraw, slab, truncation.unwrap_or(0));
+        assert!(stitch.is_some());
+        let output = run_job(job(from + redraw, slab, stitch, truncation), &mut cascades, &resolution, SdfVoxelization::Conservative);
+        check_output(&output, &[instance()]);
+    }
+
+    // a copy of the static caster, moved by offset
+    fn moved_instance(offset: Vec3) -> CpuInstance {
+        CpuInstance {
+            transform: Mat4::from_translation(offset),
+            ..instance()
+        }
+    }
+
+    fn full_region() -> (IVec3, IVec3) {
+        (IVec3::ZERO, IVec3::splat(4))
+    }
+
+    #[test]
+    fn full_build_matches_brute_force() {
+        ComputeTaskPool::init(TaskPool::default);
+        for truncation in [None, Some(3)] {
+            let mut cascades = vec![CpuCascade::default()];
+            let output = run_job(job(IVec3::ZERO, full_region(), None, truncation), &mut cascades, &resolution(), SdfVoxelization::Conservative);
+            check_output(&output, &[instance()]);
+        }
+    }
+
+    #[test]
+    fn dynamic_layer_matches_brute_force() {
+        ComputeTaskPool::init(TaskPool::default);
+        for truncation in [None, Some(3)] {
+            let mut cascades = vec![CpuCascade::default()];
+            let dynamic = moved_instance(Vec3::new(-1.2, 0.4, 0.7));
+            let full = CpuJob {
+                dynamic_instances: Some(vec![dynamic.clone()]),
+                ..job(IVec3::ZERO, full_region(), None, truncation)
+            };
+            let output = run_job(full, &mut cascades, &resolution(), SdfVoxelization::Conservative);
+            check_output(&output, &[instance(), dynamic]);
+
+            // the dynamic casters move, the static layer is kept
+            let dynamic = moved_instance(Vec3::new(-0.8, 0.3, 0.2));
+            let dynamic_only = CpuJob {
+                static_region: None,
+                dynamic_instances: Some(vec![dynamic.clone()]),
+                ..job(IVec3::ZERO, full_region(), None, truncation)
+            };
+            let output = run_job(dynamic_only, &mut cascades, &resolution(), SdfVoxelization::Conservative);
+            check_output(&output, &[instance(), dynamic]);
+        }
+    }
+
+    #[test]
+    fn stitched_scroll_matches_brute_force() {
+        scroll(IVec3::ZERO, IVec3::X, None);
+        scroll(IVec3::new(1, 0, 1), IVec3::NEG_Z, None);
+    }
+
+    #[test]
+    fn truncated_stitched_scroll_matches_brute_force() {
+        scroll(IVec3::ZERO, IVec3::X, Some(3));
+        scroll(IVec3::new(1, 0, 1), IVec3::NEG_Z, Some(3));
+    }
+}
@@ -0,0 +1,466 @@
+// batched point and ray queries against the output texture (gpu backend only, the cpu backend can use SceneSdf
+// directly). batches submitted during a frame are dispatched together after that frame's cascade updates, and the
+// results come back as SdfQueryResponse events once the readback maps, usually 1-2 frames later
+
+use super::*;
+
+// results per dispatch are copied back through a few buffers, so a new batch can go out every frame while older
+// ones are still mapping. batches wait in the render world when they are all busy
+const QUERY_SLOTS: usize = 3;
+// batches waiting past this (no output texture yet, or the readbacks are stuck) are dropped oldest first
+const MAX_QUEUED_BATCHES: usize = 256;
+// size of QueryResult in query.wgsl
+const QUERY_RESULT_SIZE: u64 = 48;
+// QUERY_* flags in query.wgsl
+const QUERY_COVERED: u32 = 1;
+const QUERY_HIT: u32 = 2;
+
+pub fn setup_gpu_queries(app: &mut App) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    app
+    .init_resource::<SdfQueries>()
+    .add_event::<SdfQueryResponse>()
+    .insert_resource(SdfQueryReceiver(Mutex::new(receiver)))
+    .add_system_to_stage(CoreStage::First, clear_sdf_queries)
+    .add_system_to_stage(CoreStage::PreUpdate, receive_sdf_queries)
+    ;
+
+    let render_app = app.sub_app_mut(RenderApp);
+    render_app
+        .init_resource::<SdfQueryPipeline>()
+        .insert_resource(SdfQueryData::new(sender))
+        .add_system_to_stage(RenderStage::Extract, extract_sdf_queries)
+        .add_system_to_stage(RenderStage::Queue, queue_sdf_queries.after(sample::queue_sdf_sample_bind_group));
+
+    let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+    render_graph.add_node("scene_sdf_query_node", SdfQueryNode);
+    render_graph
+        .add_node_edge(
+            "scene_sdf_node",
+            "scene_sdf_query_node",
+        )
+        .unwrap();
+    render_graph
+        .add_node_edge(
+            "scene_sdf_query_node",
+            bevy::render::main_graph::node::CAMERA_DRIVER,
+        )
+        .unwrap();
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SdfQuery {
+    // distance and surface normal at a point
+    Point(Vec3),
+    // sphere trace until the distance drops below `SdfQueries::hit_threshold`, the ray leaves the sdf, or it has
+    // travelled max_distance. the direction is normalized on submission
+    Ray { origin: Vec3, direction: Vec3, max_distance: f32 },
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SdfQueryResult {
+    // the point (or the ray's origin) was inside a cascade. uncovered points report nothing else
+    pub covered: bool,
+    // the ray reached a surface
+    pub hit: bool,
+    // the query point, or where the ray hit / stopped
+    pub point: Vec3,
+    // the distance at a point query, or how far the ray travelled
+    pub distance: f32,
+    // normalized gradient at the point or hit, zero for misses and where the gradient vanishes
+    pub normal: Vec3,
+    // sphere tracing steps taken
+    pub steps: u32,
+}
+
+// identifies the response to a batch
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SdfQueryBatch(u64);
+
+// results in the same order as the submitted queries. empty if the batch was dropped (see MAX_QUEUED_BATCHES) or
+// its results couldn't be read back
+#[derive(Clone, Debug)]
+pub struct SdfQueryResponse {
+    pub batch: SdfQueryBatch,
+    pub results: Vec<SdfQueryResult>,
+}
+
+// submit batches of queries from any system after `CoreStage::First`
+#[derive(Resource)]
+pub struct SdfQueries {
+    // world units. rays stop and report a hit when the distance drops below this
+    pub hit_threshold: f32,
+    // sphere tracing steps before a ray gives up (without a hit)
+    pub max_steps: u32,
+    next_batch: u64,
+    // sent to the render world at the end of the frame
+    submitted: Vec<(SdfQueryBatch, Vec<SdfQuery>)>,
+}
+
+impl Default for SdfQueries {
+    fn default() -> Self {
+        Self {
+            hit_threshold: 0.01,
+            max_steps: 128,
+            next_batch: 0,
+            submitted: Vec::new(),
+        }
+    }
+}
+
+impl SdfQueries {
+    pub fn submit(&mut self, queries: impl IntoIterator<Item = SdfQuery>) -> SdfQueryBatch {
+        let batch = SdfQueryBatch(self.next_batch);
+        self.next_batch += 1;
+
+        let queries = queries.into_iter().map(|query| match query {
+            SdfQuery::Ray { origin, direction, max_distance } => SdfQuery::Ray { origin, direction: direction.normalize_or_zero(), max_distance },
+            point => point,
+        }).collect();
+        self.submitted.push((batch, queries));
+        batch
+    }
+}
+
+fn clear_sdf_queries(mut queries: ResMut<SdfQueries>) {
+    queries.submitted.clear();
+}
+
+#[derive(Resource)]
+struct SdfQueryReceiver(Mutex<Receiver<SdfQueryResponse>>);
+
+fn receive_sdf_queries(
+    receiver: Res<SdfQueryReceiver>,
+    mut responses: EventWriter<SdfQueryResponse>,
+) {
+    let receiver = receiver.0.lock().unwrap();
+    while let Ok(response) = receiver.try_recv() {
+        responses.send(response);
+    }
+}
+
+#[derive(ShaderType, Default, Clone)]
+struct GpuQuery {
+    origin: Vec4,
+    direction: Vec4,
+}
+
+#[derive(ShaderType, Default)]
+struct GpuQueries {
+    count: u32,
+    hit_threshold: f32,
+    max_steps: u32,
+    #[size(runtime)]
+    queries: Vec<GpuQuery>,
+}
+
+struct QuerySlot {
+    queries: StorageBuffer<GpuQueries>,
+    results: Option<Buffer>,
+    readback: Option<Buffer>,
+    // in results
+    capacity: u64,
+    bind_group: Option<BindGroup>,
+    // batches in the dispatch, with their query counts
+    batches: Vec<(SdfQueryBatch, usize)>,
+    count: u32,
+    state: ReadbackState,
+    mapped: Arc<AtomicBool>,
+    map_failed: Arc<AtomicBool>,
+}
+
+impl Default for QuerySlot {
+    fn default() -> Self {
+        Self {
+            queries: Default::default(),
+            results: None,
+            readback: None,
+            capacity: 0,
+            bind_group: None,
+            batches: Vec::new(),
+            count: 0,
+            state: ReadbackState::Idle,
+            mapped: Default::default(),
+            map_failed: Default::default(),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct SdfQueryData {
+    sender: Mutex<Sender<SdfQueryResponse>>,
+    hit_threshold: f32,
+    max_steps: u32,
+    // waiting for a free slot, or for the output texture
+    queued: Vec<(SdfQueryBatch, Vec<SdfQuery>)>,
+    slots: Vec<QuerySlot>,
+    // slot to dispatch this frame
+    dispatch: Option<usize>,
+}
+
+impl SdfQueryData {
+    fn new(sender: Sender<SdfQueryResponse>) -> Self {
+        Self {
+            sender: Mutex::new(sender),
+            hit_threshold: 0.0,
+            max_steps: 0,
+            queued: Vec::new(),
+            slots: Vec::new(),
+            dispatch: None,
+        }
+    }
+}
+
+// empty responses for batches that won't get results
+fn fail_batches(sender: &Mutex<Sender<SdfQueryResponse>>, batches: impl IntoIterator<Item = SdfQueryBatch>) {
+    let sender = sender.lock().unwrap();
+    for batch in batches {
+        // the receiver only goes away with the app
+        let _ = sender.send(SdfQueryResponse {
+            batch,
+            results: Vec::new(),
+        });
+    }
+}
+
+fn extract_sdf_queries(
+    queries: Extract<Res<SdfQueries>>,
+    mut data: ResMut<SdfQueryData>,
+) {
+    data.hit_threshold = queries.hit_threshold;
+    data.max_steps = queries.max_steps;
+    data.queued.extend(queries.submitted.iter().cloned());
+
+    if data.queued.len() > MAX_QUEUED_BATCHES {
+        let dropped: Vec<_> = data.queued.drain(..data.queued.len() - MAX_QUEUED_BATCHES).map(|(batch, _)| batch).collect();
+        warn!("sdf query: {} batches waiting, dropping the oldest {}", MAX_QUEUED_BATCHES + dropped.len(), dropped.len());
+        fail_batches(&data.sender, dropped);
+    }
+}
+
+#[derive(Resource)]
+struct SdfQueryPipeline {
+    layout: BindGroupLayout,
+    pipeline: CachedComputePipelineId,
+}
+
+impl FromWorld for SdfQueryPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+        let storage = |binding: u32, read_only: bool| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("sdf query layout"),
+            entries: &[
+                // queries
+                storage(0, true),
+                // results
+                storage(1, false),
+            ],
+        });
+        // the sdf itself is read through sdf::sample at group 1
+        let sample_layout = world.resource::<SdfSampleBindGroup>().layout.clone();
+
+        let shader = world.resource::<AssetServer>().load("shader/query.wgsl");
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("sdf query pipeline".into()),
+            layout: Some(vec![layout.clone(), sample_layout]),
+            shader,
+            shader_defs: vec![],
+            entry_point: Cow::from("query"),
+        });
+
+        Self { layout, pipeline }
+    }
+}
+
+fn read_result(bytes: &[u8]) -> SdfQueryResult {
+    let f = |offset: usize| f32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+    let u = |offset: usize| u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+    let flags = u(32);
+    SdfQueryResult {
+        covered: flags & QUERY_COVERED != 0,
+        hit: flags & QUERY_HIT != 0,
+        point: Vec3::new(f(0), f(4), f(8)),
+        distance: f(12),
+        normal: Vec3::new(f(16), f(20), f(24)),
+        steps: u(36),
+    }
+}
+
+fn queue_sdf_queries(
+    sample: Res<SdfSampleBindGroup>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    pipeline: Res<SdfQueryPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    mut data: ResMut<SdfQueryData>,
+) {
+    let data = &mut *data;
+    data.dispatch = None;
+
+    // collect finished dispatches
+    for slot in data.slots.iter_mut() {
+        match slot.state {
+            ReadbackState::Idle => (),
+            ReadbackState::Copied => {
+                let (mapped, map_failed) = (slot.mapped.clone(), slot.map_failed.clone());
+                device.map_buffer(&slot.readback.as_ref().unwrap().slice(..), wgpu::MapMode::Read, move |res| {
+                    match res {
+                        Ok(_) => mapped.store(true, Ordering::Release),
+                        Err(e) => {
+                            error!("sdf query: failed to map results: {e}");
+                            map_failed.store(true, Ordering::Release);
+                        }
+                    }
+                });
+                slot.state = ReadbackState::Mapping;
+            }
+            ReadbackState::Mapping => {
+                if slot.map_failed.swap(false, Ordering::Acquire) {
+                    // the buffer was never mapped, so the slot can be reused straight away
+                    fail_batches(&data.sender, slot.batches.drain(..).map(|(batch, _)| batch));
+                    slot.state = ReadbackState::Idle;
+                } else if slot.mapped.swap(false, Ordering::Acquire) {
+                    let readback = slot.readback.as_ref().unwrap();
+                    let range = readback.slice(..).get_mapped_range();
+                    let mut results = range.chunks(QUERY_RESULT_SIZE as usize).map(read_result);
+                    let sender = data.sender.lock().unwrap();
+                    for (batch, count) in slot.batches.drain(..) {
+                        // the receiver only goes away with the app
+                        let _ = sender.send(SdfQueryResponse {
+                            batch,
+                            results: results.by_ref().take(count).collect(),
+                        });
+                    }
+                    drop(range);
+                    readback.unmap();
+                    slot.state = ReadbackState::Idle;
+                }
+            }
+        }
+    }
+
+    if data.queued.is_empty() || pipeline_cache.get_compute_pipeline(pipeline.pipeline).is_none() {
+        return;
+    }
+    if sample.bind_group.is_none() {
+        return;
+    }
+
+    let index = match data.slots.iter().position(|slot| matches!(slot.state, ReadbackState::Idle)) {
+        Some(index) => index,
+        None if data.slots.len() < QUERY_SLOTS => {
+            data.slots.push(QuerySlot::default());
+            data.slots.len() - 1
+        }
+        None => return,
+    };
+    let slot = &mut data.slots[index];
+
+    // everything queued goes out together
+    let mut queries = Vec::new();
+    for (batch, batch_queries) in data.queued.drain(..) {
+        slot.batches.push((batch, batch_queries.len()));
+        queries.extend(batch_queries.into_iter().map(|query| match query {
+            SdfQuery::Point(point) => GpuQuery {
+                origin: point.extend(0.0),
+                direction: Vec4::ZERO,
+            },
+            SdfQuery::Ray { origin, direction, max_distance } => GpuQuery {
+                origin: origin.extend(1.0),
+                direction: direction.extend(max_distance),
+            },
+        }));
+    }
+
+    slot.count = queries.len() as u32;
+    slot.queries.set(GpuQueries {
+        count: slot.count,
+        hit_threshold: data.hit_threshold,
+        max_steps: data.max_steps,
+        queries,
+    });
+    slot.queries.write_buffer(&device, &queue);
+
+    let size = (slot.count as u64).max(1) * QUERY_RESULT_SIZE;
+    if slot.capacity < size {
+        slot.capacity = size.next_power_of_two();
+        slot.results = Some(device.create_buffer(&BufferDescriptor {
+            label: Some("sdf query results"),
+            size: slot.capacity,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        }));
+        slot.readback = Some(device.create_buffer(&BufferDescriptor {
+            label: Some("sdf query readback"),
+            size: slot.capacity,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    }
+
+    slot.bind_group = Some(device.create_bind_group(&BindGroupDescriptor {
+        label: Some("sdf query bindgroup"),
+        layout: &pipeline.layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: slot.queries.binding().unwrap(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: slot.results.as_ref().unwrap().as_entire_binding(),
+            },
+        ],
+    }));
+
+    slot.state = ReadbackState::Copied;
+    data.dispatch = Some(index);
+}
+
+// runs the dispatch picked by queue_sdf_queries, after SceneSdfNode has written this frame's updates
+struct SdfQueryNode;
+
+impl Node for SdfQueryNode {
+    fn run(
+        &self,
+        _graph: &mut bevy::render::render_graph::RenderGraphContext,
+        render_context: &mut bevy::render::renderer::RenderContext,
+        world: &World,
+    ) -> Result<(), bevy::render::render_graph::NodeRunError> {
+        let data = world.resource::<SdfQueryData>();
+        let Some(slot) = data.dispatch.map(|index| &data.slots[index]) else {
+            return Ok(());
+        };
+
+        // checked in queue_sdf_queries
+        let pipeline = world.resource::<PipelineCache>().get_compute_pipeline(world.resource::<SdfQueryPipeline>().pipeline).unwrap();
+
+        let mut pass = render_context
+            .command_encoder
+            .begin_compute_pass(&ComputePassDescriptor::default());
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, slot.bind_group.as_ref().unwrap(), &[]);
+        // checked in queue_sdf_queries
+        pass.set_bind_group(1, world.resource::<SdfSampleBindGroup>().bind_group.as_ref().unwrap(), &[]);
+        pass.dispatch_workgroups((slot.count + 63) / 64, 1, 1);
+        drop(pass);
+
+        let size = slot.count as u64 * QUERY_RESULT_SIZE;
+        if size > 0 {
+            render_context.command_encoder.copy_buffer_to_buffer(slot.results.as_ref().unwrap(), 0, slot.readback.as_ref().unwrap(), 0, size);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+// bindings for the sdf::sample shader module (assets/shader/sample.wgsl), so materials, render nodes and compute
+// passes can read the scene sdf
+
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    render::{
+        render_phase::{EntityRenderCommand, RenderCommandResult, TrackedRenderPass},
+        render_resource::TextureSampleType,
+    },
+};
+use wgpu::DownlevelFlags;
+
+use super::*;
+
+pub fn setup_sdf_sample(app: &mut App) {
+    app.sub_app_mut(RenderApp)
+        .init_resource::<SdfSampleBindGroup>()
+        .add_system_to_stage(RenderStage::Queue, queue_sdf_sample_bind_group.after(queue_sdf_data));
+}
+
+// render world. pipelines importing sdf::sample put `layout` at group 1 (or 3 with the SDF_SAMPLE_GROUP_3 shader
+// def). `bind_group` is None until the output texture exists and is rebuilt every frame in RenderStage::Queue, so
+// fetch it when drawing rather than keeping it.
+// `layout` is visible to fragment and compute shaders. vertex shaders reading the sdf use `vertex_layout` and
+// `vertex_bind_group` instead, which are None on adapters without DownlevelFlags::VERTEX_STORAGE
+#[derive(Resource)]
+pub struct SdfSampleBindGroup {
+    pub layout: BindGroupLayout,
+    pub bind_group: Option<BindGroup>,
+    pub vertex_layout: Option<BindGroupLayout>,
+    pub vertex_bind_group: Option<BindGroup>,
+}
+
+fn sample_layout(device: &RenderDevice, label: &'static str, visibility: ShaderStages) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &[
+            // cascade infos
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(SdfCascadeInfos::min_size()),
+                },
+                count: None,
+            },
+            // sdf texture, loaded without a sampler so every output format works
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D3,
+                    multisampled: false,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+impl FromWorld for SdfSampleBindGroup {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+        let layout = sample_layout(device, "sdf sample layout", ShaderStages::FRAGMENT | ShaderStages::COMPUTE);
+
+        // storage buffers in vertex shaders are a downlevel feature (not on webgl2 for one)
+        let vertex_storage = world
+            .get_resource::<RenderAdapter>()
+            .map(|adapter| adapter.get_downlevel_capabilities().flags.contains(DownlevelFlags::VERTEX_STORAGE))
+            .unwrap_or(false);
+        let vertex_layout = vertex_storage.then(|| {
+            sample_layout(device, "sdf sample vertex layout", ShaderStages::VERTEX | ShaderStages::FRAGMENT | ShaderStages::COMPUTE)
+        });
+
+        Self {
+            layout,
+            bind_group: None,
+            vertex_layout,
+            vertex_bind_group: None,
+        }
+    }
+}
+
+pub(crate) fn queue_sdf_sample_bind_group(
+    device: Res<RenderDevice>,
+    output_data: Res<SdfOutputData>,
+    mut sample: ResMut<SdfSampleBindGroup>,
+) {
+    let sample = &mut *sample;
+    sample.bind_group = None;
+    sample.vertex_bind_group = None;
+
+    let (Some((_, output_view, _)), Some(header)) = (output_data.output_buffer.as_ref(), output_data.sdf_header_buffer.binding()) else {
+        return;
+    };
+
+    let bind_group = |label: &'static str, layout: &BindGroupLayout| device.create_bind_group(&BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: header.clone(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(output_view),
+            },
+        ],
+    });
+    sample.bind_group = Some(bind_group("sdf sample bindgroup", &sample.layout));
+    sample.vertex_bind_group = sample.vertex_layout.as_ref().map(|layout| bind_group("sdf sample vertex bindgroup", layout));
+}
+
+// sets SdfSampleBindGroup at group I, for draw functions of pipelines that import sdf::sample. with VERTEX = true
+// it sets the vertex variant, and fails where that isn't supported
+pub struct SetSdfSampleBindGroup<const I: usize, const VERTEX: bool = false>;
+
+impl<const I: usize, const VERTEX: bool> EntityRenderCommand for SetSdfSampleBindGroup<I, VERTEX> {
+    type Param = SRes<SdfSampleBindGroup>;
+
+    fn render<'w>(
+        _view: Entity,
+        _item: Entity,
+        sample: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let sample = sample.into_inner();
+        let bind_group = match VERTEX {
+            true => sample.vertex_bind_group.as_ref(),
+            false => sample.bind_group.as_ref(),
+        };
+        match bind_group {
+            Some(bind_group) => {
+                pass.set_bind_group(I, bind_group, &[]);
+                RenderCommandResult::Success
+            }
+            None => RenderCommandResult::Failure,
+        }
+    }
+}